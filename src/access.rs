@@ -0,0 +1,110 @@
+use std::{fmt::Display, str::FromStr};
+
+use rand::{distributions::Alphanumeric, Rng};
+use redis::{aio::MultiplexedConnection, AsyncCommands, ExistenceCheck, RedisResult, SetExpiry, SetOptions};
+use teloxide::types::{ChatId, UserId};
+
+use crate::{config, handlers::command::Action, schema};
+
+/// Set of the chats allowed to use the bot in invite-only mode
+const ALLOWED: &str = "allowed";
+/// Seconds an invite code can be used
+const INVITE_TTL: u64 = 7 * 86400;
+/// Seconds the commands of a user are counted for the rate limit
+const RATE_LIMIT_WINDOW: u64 = 60;
+
+fn var<T: FromStr>(key: &str) -> Option<T>
+where
+    T::Err: Display
+{
+    config::var(key).ok().and_then(|value| value.parse().inspect_err(|e| log::error!("Invalid {key}: {e}")).ok())
+}
+
+/// Maximum subscriptions of a chat, to lives and appearances together, set by `MAX_SUBSCRIPTIONS`
+pub fn max_subscriptions() -> Option<usize> {
+    var("MAX_SUBSCRIPTIONS")
+}
+
+/// Maximum commands of a user per minute, set by `COMMAND_RATE_LIMIT`, 20 by default and unlimited if 0
+fn command_rate_limit() -> Option<u64> {
+    Some(var("COMMAND_RATE_LIMIT").unwrap_or(20)).filter(|limit| *limit > 0)
+}
+
+/// Whether only the chats allowed by the owner can use the bot, enabled by `INVITE_ONLY=on`
+pub fn invite_only() -> bool {
+    config::var("INVITE_ONLY").is_ok_and(|value| matches!(value.as_str(), "on" | "true" | "1"))
+}
+
+/// Result of counting a command against the rate limit of its user
+#[derive(Debug, PartialEq)]
+pub enum RateLimit {
+    Allowed,
+    /// First command over the limit, the user is told to slow down
+    Exceeded,
+    /// The user has already been told, the command is ignored
+    Ignored
+}
+
+pub async fn count_command(db: &mut MultiplexedConnection, user_id: UserId) -> RedisResult<RateLimit> {
+    let Some(limit) = command_rate_limit() else {
+        return Ok(RateLimit::Allowed);
+    };
+    let key = schema::key(format!("rate_limit:{user_id}"));
+    // the counter starts with its expiry in the same transaction, so it can't be left without one
+    let options = SetOptions::default().conditional_set(ExistenceCheck::NX)
+        .with_expiration(SetExpiry::EX(RATE_LIMIT_WINDOW));
+    let (count,): (u64,) = redis::pipe().atomic()
+        .set_options(&key, 0, options).ignore()
+        .incr(&key, 1)
+        .query_async(db).await?;
+    Ok(match count {
+        count if count <= limit => RateLimit::Allowed,
+        count if count == limit + 1 => RateLimit::Exceeded,
+        _ => RateLimit::Ignored
+    })
+}
+
+/// Subscriptions the chat can still add, `None` if it is unlimited
+pub async fn remaining_subscriptions(db: &mut MultiplexedConnection, chat_id: ChatId) -> RedisResult<Option<usize>> {
+    let Some(max) = max_subscriptions() else {
+        return Ok(None);
+    };
+    let (subs, appearances): (usize, usize) = redis::pipe()
+        .scard(Action::Subscribe.chat_key(chat_id)).scard(Action::Appear.chat_key(chat_id))
+        .query_async(db).await?;
+    Ok(Some(max.saturating_sub(subs + appearances)))
+}
+
+/// Whether the chat can use the bot, which is always the case unless the bot is invite-only
+pub async fn is_allowed(db: &mut MultiplexedConnection, chat_id: ChatId) -> RedisResult<bool> {
+    if !invite_only() || config::var("BOT_OWNER").is_ok_and(|owner| owner == chat_id.to_string()) {
+        return Ok(true);
+    }
+    db.sismember(schema::key(ALLOWED), chat_id.to_string()).await
+}
+
+pub async fn allow(db: &mut MultiplexedConnection, chat_id: ChatId) -> RedisResult<bool> {
+    db.sadd(schema::key(ALLOWED), chat_id.to_string()).await
+}
+
+/// Remove the chat from the allowed chats, returning whether it was allowed
+pub async fn disallow(db: &mut MultiplexedConnection, chat_id: ChatId) -> RedisResult<bool> {
+    db.srem(schema::key(ALLOWED), chat_id.to_string()).await
+}
+
+/// Create a single-use invite code, valid for a week
+pub async fn invite(db: &mut MultiplexedConnection) -> RedisResult<String> {
+    let code: String = rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect();
+    let _: () = db.set_ex(schema::key(format!("invite:{code}")), 1, INVITE_TTL).await?;
+    Ok(code)
+}
+
+/// Allow the chat if the invite code is valid, returning whether it was
+pub async fn redeem(db: &mut MultiplexedConnection, code: &str, chat_id: ChatId) -> RedisResult<bool> {
+    let invite: Option<String> = db.get_del(schema::key(format!("invite:{code}"))).await?;
+    if invite.is_none() {
+        return Ok(false);
+    }
+    allow(db, chat_id).await?;
+    Ok(true)
+}
@@ -1,9 +1,11 @@
-use std::{env, fmt::Display, sync::Arc};
+use std::{fmt::Display, sync::Arc};
 
 use bilibili::BilibiliAPI;
+use chrono::{DateTime, Duration, Utc};
 use cookies::SimpleCookieJar;
+use error::Error;
 use redis::ToRedisArgs;
-use reqwest::{header::HeaderMap, Client};
+use reqwest::{header::HeaderMap, Client, Response};
 use serde::Serialize;
 use serde_json::Value;
 use strum_macros::EnumString;
@@ -12,9 +14,16 @@ use tokio::sync::OnceCell;
 use twitter::TwitterAPI;
 use url::Url;
 
-use crate::{log_utils::LogResult, subscription::Subscription};
+use crate::{
+    config,
+    metrics::{API_REQUESTS, API_REQUEST_DURATION},
+    platform::Platform,
+    subscription::Subscription,
+    templates::Placeholders
+};
 
 mod cookies;
+pub mod error;
 pub mod twitter;
 pub mod bilibili;
 
@@ -28,46 +37,93 @@ pub enum LiveState {
 }
 
 pub trait Metadata {
-    type Id: ToRedisArgs;
+    type Id: ToRedisArgs + Display;
 
     fn get_id(&self) -> &Self::Id;
     fn get_state(&self) -> &LiveState;
+    fn get_title(&self) -> &str;
+    fn get_url(&self) -> &Url;
+    fn get_cover(&self) -> Option<&Url>;
+    /// Start time of the live, which some platforms stop reporting once it has ended
+    fn get_start_time(&self) -> Option<DateTime<Utc>>;
+    fn set_start_time(&mut self, start_time: DateTime<Utc>);
     fn get_attachment(&self) -> InputFile;
     fn to_sub(&self) -> Subscription;
+    /// Values of the notification template placeholders
+    fn placeholders(&self) -> Placeholders;
 }
 
-pub trait API<T: Metadata + Display> {
-    async fn live_status(&self, live_id: &String, language: Option<String>) -> Option<T>;
-    async fn user_live_status(&self, subs: Vec<Subscription>) -> Vec<T>;
+/// Requests an API sends at the same time when checking several lives
+pub const CONCURRENCY: usize = 4;
+
+// only implemented by the clients of this crate, whose futures are all Send
+#[allow(async_fn_in_trait)]
+pub trait API<T: Metadata> {
+    async fn live_status(&self, live_id: &String, language: Option<String>) -> Result<T, Error>;
+    /// Running lives of the subscriptions, and the subscriptions which couldn't be checked
+    async fn user_live_status(&self, subs: Vec<Subscription>) -> (Vec<T>, Vec<(Subscription, Error)>);
+}
+
+/// Format a time for notifications
+pub fn fmt_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+/// Format a duration as `H:MM:SS`
+pub fn fmt_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds % 3600 / 60, seconds % 60)
 }
 
 pub struct APIClient {
+    /// Platform the requests are counted for in the metrics
+    platform: Platform,
     base_url: Url,
     client: Client
 }
 
 impl APIClient {
-    pub fn new(base_url: &str, headers: HeaderMap, cookies: Option<SimpleCookieJar>) -> Self {
-        let mut cb = Client::builder().default_headers(headers);
+    /// Timeout of the requests, so a stalled connection doesn't hang the watcher
+    const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+    pub fn new(platform: Platform, base_url: &str, headers: HeaderMap, cookies: Option<SimpleCookieJar>) -> Self {
+        let mut cb = Client::builder().default_headers(headers).timeout(Self::TIMEOUT);
         if let Some(cookies) = cookies {
             cb = cb.cookie_provider(cookies.into());
         }
         let client = cb.build().expect("Failed to create API client");
-        Self { base_url: base_url.parse().expect("Invalid base URL"), client }
+        Self { platform, base_url: base_url.parse().expect("Invalid base URL"), client }
     }
 
-    pub async fn get<T: Serialize>(&self, path: &[&str], params: Option<T>) -> Option<Value> {
-        let url = self.base_url.join(&path.join("/")).log_ok("Invalid request URL")?;
-        let mut req = self.client.get(url.clone());
+    pub async fn send<T: Serialize>(&self, path: &[&str], params: Option<T>) -> Result<Response, Error> {
+        let url = self.base_url.join(&path.join("/")).map_err(|e| Error::InvalidRequest(format!("Invalid request URL: {e}")))?;
+        let mut req = self.client.get(url);
         if let Some(params) = params {
             req = req.query(&params);
         }
-        let res = req.send().await.log_ok("API error")?;
-        if res.status().is_success() {
-            return res.json().await.log_ok("JSON decode error");
+        let platform = self.platform.short_name();
+        let timer = API_REQUEST_DURATION.with_label_values(&[platform]).start_timer();
+        let res = req.send().await;
+        timer.observe_duration();
+        let status = res.as_ref().map_or("error".to_owned(), |res| res.status().as_u16().to_string());
+        API_REQUESTS.with_label_values(&[platform, &status]).inc();
+        Ok(res?)
+    }
+
+    pub async fn get<T: Serialize>(&self, path: &[&str], params: Option<T>) -> Result<Value, Error> {
+        let res = self.send(path, params).await?;
+        if !res.status().is_success() {
+            return Err(Error::from_response(&res));
+        }
+        Ok(res.json().await?)
+    }
+
+    pub async fn get_text(&self, path: &[&str]) -> Result<String, Error> {
+        let res = self.send::<()>(path, None).await?;
+        if !res.status().is_success() {
+            return Err(Error::from_response(&res));
         }
-        log::error!("{}: {}: {:?}", url, res.status(), res);
-        None
+        Ok(res.text().await?)
     }
 }
 
@@ -75,15 +131,59 @@ pub static TWITTER_API: OnceCell<Arc<TwitterAPI>> = OnceCell::const_new();
 pub static BILIBILI_API: OnceCell<Arc<BilibiliAPI>> = OnceCell::const_new();
 const ENV_ERROR_MSG: &str = "Failed to load token from environment variables";
 
+/// Pairs of `auth_token` and `ct0` cookies of the Twitter accounts, given as comma-separated lists by
+/// `TWITTER_AUTH_TOKEN` and `TWITTER_CSRF_TOKEN`
+pub fn twitter_credentials() -> Result<Vec<(String, String)>, String> {
+    let var = |key| config::var(key).map_err(|e| format!("{key}: {e}"));
+    pair_credentials(&var("TWITTER_AUTH_TOKEN")?, &var("TWITTER_CSRF_TOKEN")?)
+}
+
+/// Pair the tokens of the comma-separated lists, which must have the same length so no account gets the CSRF token
+/// of another
+fn pair_credentials(auth_tokens: &str, csrf_tokens: &str) -> Result<Vec<(String, String)>, String> {
+    let auth_tokens: Vec<_> = auth_tokens.split(',').map(str::trim).collect();
+    let csrf_tokens: Vec<_> = csrf_tokens.split(',').map(str::trim).collect();
+    if auth_tokens.len() != csrf_tokens.len() {
+        return Err(format!(
+            "{} auth tokens but {} CSRF tokens, TWITTER_AUTH_TOKEN and TWITTER_CSRF_TOKEN must list the same accounts",
+            auth_tokens.len(),
+            csrf_tokens.len()
+        ));
+    }
+    Ok(auth_tokens.into_iter().zip(csrf_tokens)
+        .map(|(auth_token, csrf_token)| (auth_token.to_owned(), csrf_token.to_owned()))
+        .collect())
+}
+
 pub async fn get_twitter_api() -> &'static Arc<TwitterAPI> {
     TWITTER_API.get_or_init(async || {
-        TwitterAPI::new(
-            &env::var("TWITTER_AUTH_TOKEN").expect(ENV_ERROR_MSG),
-            &env::var("TWITTER_CSRF_TOKEN").expect(ENV_ERROR_MSG)
-        ).into()
+        let credentials = twitter_credentials().expect(ENV_ERROR_MSG);
+        let api_url = config::var("TWITTER_API_URL").unwrap_or("https://x.com/i/api/".to_owned());
+        let web_url = config::var("TWITTER_WEB_URL").unwrap_or("https://x.com/".to_owned());
+        TwitterAPI::new(&api_url, &web_url, &credentials).into()
     }).await
 }
 
 pub async fn get_bilibili_api() -> &'static Arc<BilibiliAPI> {
-    BILIBILI_API.get_or_init(async || { BilibiliAPI::new().into() }).await
+    BILIBILI_API.get_or_init(async || {
+        let api_url = config::var("BILIBILI_API_URL").unwrap_or("https://api.live.bilibili.com".to_owned());
+        let nav_url = config::var("BILIBILI_NAV_URL")
+            .unwrap_or("https://api.bilibili.com/x/web-interface/nav".to_owned());
+        BilibiliAPI::new(&api_url, &nav_url).into()
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn pair_credentials() {
+        assert_eq!(super::pair_credentials("a, b", "c,d").unwrap(), [
+            ("a".to_owned(), "c".to_owned()),
+            ("b".to_owned(), "d".to_owned())
+        ]);
+        assert_eq!(
+            super::pair_credentials("a,b", "c").unwrap_err(),
+            "2 auth tokens but 1 CSRF tokens, TWITTER_AUTH_TOKEN and TWITTER_CSRF_TOKEN must list the same accounts"
+        );
+    }
 }
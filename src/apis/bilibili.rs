@@ -1,26 +1,27 @@
-use std::{collections::BTreeMap, fmt::Display, sync::Arc};
+use std::collections::BTreeMap;
 
 use base16ct::lower::encode_string;
 use chrono::{DateTime, Duration, Utc};
 use futures::{stream, StreamExt};
 use lazy_regex::{lazy_regex, Lazy};
 use md5::{Digest, Md5};
+use models::{InfoByRoom, Nav, Response, Status};
 use regex::Regex;
 use reqwest::header::{self, HeaderMap, HeaderValue};
-use serde_json::Value;
-use teloxide::{
-    types::InputFile,
-    utils::markdown::{bold, escape, link}
-};
-use tokio::sync::{Mutex, OnceCell};
+use teloxide::types::InputFile;
+use tokio::sync::Mutex;
 use url::Url;
 
-use super::{APIClient, LiveState, Metadata, API};
+use super::{error::{decode, Error}, fmt_duration, fmt_time, APIClient, LiveState, Metadata, API, CONCURRENCY};
 use crate::{
+    metrics::WBI_KEY_REFRESHES,
     platform::{Platform, User},
-    subscription::Subscription
+    subscription::Subscription,
+    templates::{Placeholder, Placeholders}
 };
 
+mod models;
+
 struct Wbi {
     client: APIClient,
     update_time: DateTime<Utc>,
@@ -37,56 +38,62 @@ impl Wbi {
     ];
     const KEY_LENGTH: usize = 32;
 
-    fn new() -> Self {
+    fn new(nav_url: &str) -> Self {
         let headers = HeaderMap::from_iter([
             (header::USER_AGENT, HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/132.0.0.0 Safari/537.36 Edg/132.0.0.0"))
         ]);
         Self {
-            client: APIClient::new("https://api.bilibili.com/x/web-interface/nav", headers, None),
+            client: APIClient::new(Platform::BilibiliLive, nav_url, headers, None),
             update_time: Utc::now(),
             key: None
         }
     }
 
-    async fn update_key(&mut self) -> Option<()> {
+    async fn update_key(&mut self) -> Result<(), Error> {
         if self.key.is_none() || self.update_time + Duration::hours(2) <= Utc::now() {
-            let data = self.client.get::<()>(&[], None).await?;
-            let img_url = data["data"]["wbi_img"]["img_url"].as_str()?;
-            let sub_url = data["data"]["wbi_img"]["sub_url"].as_str()?;
-            let img = &Self::WBI_REGEX.captures(img_url)?[1];
-            let sub = &Self::WBI_REGEX.captures(sub_url)?[1];
-            let full = img.to_owned() + sub;
-            let full = full.as_bytes();
-            let mut key = [0u8; Self::KEY_LENGTH];
-            for i in 0..Self::KEY_LENGTH {
-                key[i] = full[Self::KEY_MAP[i]];
-            }
-            self.key = Some(str::from_utf8(&key).ok()?.to_owned());
+            let key = self.fetch_key().await;
+            WBI_KEY_REFRESHES.with_label_values(&[if key.is_ok() { "success" } else { "failure" }]).inc();
+            self.key = Some(key?);
             self.update_time = Utc::now();
         }
-        Some(())
+        Ok(())
     }
 
-    async fn sign(&mut self, data: &mut BTreeMap<&str, String>) -> Option<()> {
+    async fn fetch_key(&self) -> Result<String, Error> {
+        // the status is an error for anonymous users, who are given the key anyway
+        let nav = decode::<Response<Nav>>(&self.client.get::<()>(&[], None).await?)?.data;
+        let file_name = |path: &str, url: &str| -> Result<String, Error> {
+            let captures = Self::WBI_REGEX.captures(url).ok_or_else(|| Error::schema(path, "not a WBI image URL"))?;
+            Ok(captures[1].to_owned())
+        };
+        let full = file_name("data.wbi_img.img_url", &nav.wbi_img.img_url)?
+            + &file_name("data.wbi_img.sub_url", &nav.wbi_img.sub_url)?;
+        let full = full.as_bytes();
+        if full.len() < Self::KEY_MAP.len() {
+            return Err(Error::schema("data.wbi_img", "WBI key too short"));
+        }
+        let mut key = [0u8; Self::KEY_LENGTH];
+        for i in 0..Self::KEY_LENGTH {
+            key[i] = full[Self::KEY_MAP[i]];
+        }
+        Ok(str::from_utf8(&key).map_err(|e| Error::Decode(e.to_string()))?.to_owned())
+    }
+
+    async fn sign(&mut self, data: &mut BTreeMap<&str, String>) -> Result<(), Error> {
         self.update_key().await?;
-        let key = self.key.clone()?;
+        let key = self.key.clone().unwrap_or_default();
         let wts = Utc::now().timestamp();
         data.insert("wts", wts.to_string());
-        let param = serde_urlencoded::to_string(&*data).ok()?;
+        let param = serde_urlencoded::to_string(&*data).map_err(|e| Error::InvalidRequest(e.to_string()))?;
         let hash = encode_string(&Md5::digest(param + &key));
         data.insert("w_rid", hash);
-        Some(())
+        Ok(())
     }
 }
 
-static WBI: OnceCell<Arc<Mutex<Wbi>>> = OnceCell::const_new();
-
-async fn get_wbi() -> &'static Arc<Mutex<Wbi>> {
-    WBI.get_or_init(async || { Arc::new(Mutex::new(Wbi::new())) }).await
-}
-
 pub struct BilibiliAPI {
-    client: APIClient
+    client: APIClient,
+    wbi: Mutex<Wbi>
 }
 
 #[allow(dead_code)]
@@ -97,8 +104,10 @@ pub struct BilibiliLive {
     pub creator_name: String,
     pub creator_id: u64,
     pub cover_image_url: Url,
-    pub start_time: DateTime<Utc>,
-    pub state: LiveState
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub state: LiveState,
+    pub viewers: Option<u64>
 }
 
 impl Metadata for BilibiliLive {
@@ -112,6 +121,26 @@ impl Metadata for BilibiliLive {
         &self.state
     }
 
+    fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    fn get_url(&self) -> &Url {
+        &self.url
+    }
+
+    fn get_cover(&self) -> Option<&Url> {
+        Some(&self.cover_image_url)
+    }
+
+    fn get_start_time(&self) -> Option<DateTime<Utc>> {
+        self.start_time
+    }
+
+    fn set_start_time(&mut self, start_time: DateTime<Utc>) {
+        self.start_time = Some(start_time);
+    }
+
     fn get_attachment(&self) -> InputFile {
         InputFile::url(self.cover_image_url.clone())
     }
@@ -122,83 +151,110 @@ impl Metadata for BilibiliLive {
             user: User { id: self.id.to_string(), username: self.creator_name.clone() }
         }
     }
+
+    fn placeholders(&self) -> Placeholders {
+        let mut values = Placeholders::from([
+            (Placeholder::Platform, Platform::BilibiliLive.to_string()),
+            (Placeholder::Creator, self.creator_name.clone()),
+            (Placeholder::Handle, self.creator_id.to_string()),
+            (Placeholder::ProfileUrl, format!("https://space.bilibili.com/{}", self.creator_id)),
+            (Placeholder::Title, self.title.clone()),
+            (Placeholder::Url, self.url.to_string())
+        ]);
+        if let Some(start_time) = self.start_time {
+            values.insert(Placeholder::StartTime, fmt_time(start_time));
+            if matches!(self.state, LiveState::Ended) {
+                values.insert(Placeholder::Duration, fmt_duration(self.end_time.unwrap_or(Utc::now()) - start_time));
+            }
+        }
+        if let Some(viewers) = self.viewers {
+            values.insert(Placeholder::Audience, viewers.to_string());
+        }
+        values
+    }
 }
 
 impl BilibiliAPI {
-    pub fn new() -> Self {
+    /// Client of the live API at `api_url`, e.g. `https://api.live.bilibili.com`, signing the requests with the key
+    /// given by the endpoint at `nav_url`, e.g. `https://api.bilibili.com/x/web-interface/nav`
+    pub fn new(api_url: &str, nav_url: &str) -> Self {
         let mut headers = HeaderMap::new();
         headers.append(
             header::USER_AGENT,
             HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/132.0.0.0 Safari/537.36 Edg/132.0.0.0")
         );
-        Self { client: APIClient::new("https://api.live.bilibili.com", headers, None) }
+        Self { client: APIClient::new(Platform::BilibiliLive, api_url, headers, None), wbi: Wbi::new(nav_url).into() }
     }
 
-    async fn get_info_by_room(&self, room_id: u64) -> Option<Value> {
+    async fn get_info_by_room(&self, room_id: &str) -> Result<InfoByRoom, Error> {
         let path = "/xlive/web-room/v1/index/getInfoByRoom";
+        let room_id: u64 = room_id.parse().map_err(|_| Error::InvalidRequest(format!("Invalid room ID {room_id}")))?;
         let mut params = BTreeMap::from([("room_id", room_id.to_string())]);
-        let wbi = get_wbi().await;
-        wbi.lock().await.sign(&mut params).await?;
+        self.wbi.lock().await.sign(&mut params).await?;
         let result = self.client.get(&[path], Some(params)).await?;
-        if result["code"].as_i64()? != 0 {
-            log::error!("Bilibili API error: {}", result["code"]);
-            return None;
+        match decode::<Status>(&result)? {
+            Status { code: 0, .. } => Ok(decode::<Response<InfoByRoom>>(&result)?.data),
+            // risk control, triggered by polling too often
+            Status { code: code @ (-412 | -352), message } => {
+                log::warn!("Bilibili API error {code}: {message}");
+                Err(Error::RateLimited(None))
+            }
+            Status { code, message } => Err(Error::Platform { code, message })
         }
-        Some(result)
     }
 
-    pub async fn username(&self, room_id: &str) -> Option<String> {
-        let result = self.get_info_by_room(room_id.parse().ok()?).await?;
-        Some(result["data"]["anchor_info"]["base_info"]["uname"].as_str()?.to_owned())
+    pub async fn username(&self, room_id: &str) -> Result<String, Error> {
+        Ok(self.get_info_by_room(room_id).await?.anchor_info.base_info.uname)
     }
 }
 
-impl API<BilibiliLive> for BilibiliAPI {
-    async fn live_status(&self, live_id: &String, _language: Option<String>) -> Option<BilibiliLive> {
-        let result = self.get_info_by_room(live_id.parse().ok()?).await?;
-        let info = result["data"]["room_info"].as_object()?;
-        let id = info["room_id"].as_u64()?;
-        Some(BilibiliLive {
-            id,
-            url: format!("https://live.bilibili.com/{id}").parse().ok()?,
-            title: info["title"].as_str()?.to_owned(),
-            creator_name: result["data"]["anchor_info"]["base_info"]["uname"].as_str()?.to_owned(),
-            creator_id: info["uid"].as_u64()?,
-            cover_image_url: info["cover"].as_str()?.parse().ok()?,
-            start_time: DateTime::from_timestamp(info["live_start_time"].as_i64()?, 0)?,
-            state: match info["live_status"].as_u64()? {
-                0 => LiveState::Ended,
-                1 => LiveState::Running,
-                status => LiveState::Unknown(status.to_string())
-            }
+impl BilibiliLive {
+    fn parse(info: InfoByRoom) -> Result<Self, Error> {
+        let room = info.room_info;
+        let state = match room.live_status {
+            0 => LiveState::Ended,
+            1 => LiveState::Running,
+            status => LiveState::Unknown(status.to_string())
+        };
+        Ok(BilibiliLive {
+            id: room.room_id,
+            url: format!("https://live.bilibili.com/{}", room.room_id).parse()
+                .map_err(|e: url::ParseError| Error::InvalidRequest(e.to_string()))?,
+            title: room.title,
+            creator_name: info.anchor_info.base_info.uname,
+            creator_id: room.uid,
+            cover_image_url: room.cover,
+            // live_start_time is reset to 0 once the live has ended
+            start_time: DateTime::from_timestamp(room.live_start_time, 0).filter(|t| t.timestamp() > 0),
+            end_time: matches!(state, LiveState::Ended).then(Utc::now),
+            state,
+            viewers: info.watched_show.map(|show| show.num)
         })
     }
+}
 
-    async fn user_live_status(&self, subs: Vec<Subscription>) -> Vec<BilibiliLive> {
-        stream::iter(subs).filter_map(
-            async |sub| self.live_status(&sub.user.id, None).await.filter(|live| matches!(live.state, LiveState::Running))
-        ).collect().await
+impl API<BilibiliLive> for BilibiliAPI {
+    async fn live_status(&self, live_id: &String, _language: Option<String>) -> Result<BilibiliLive, Error> {
+        BilibiliLive::parse(self.get_info_by_room(live_id).await?)
     }
-}
 
-impl Display for BilibiliLive {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.state {
-            LiveState::Running => write!(
-                f,
-                "{} \\({}\\)'s Bilibili Live started\n{}",
-                bold(escape(self.creator_name.as_str()).as_str()),
-                link(format!("https://space.bilibili.com/{}", self.creator_id).as_str(), self.creator_id.to_string().as_str()),
-                link(self.url.as_str(), escape(self.title.as_str()).as_str())
-            ),
-            LiveState::Ended => write!(
-                f,
-                "{} \\({}\\)'s Bilibili Live ended",
-                bold(escape(self.creator_name.as_str()).as_str()),
-                link(format!("https://space.bilibili.com/{}", self.creator_id).as_str(), self.creator_id.to_string().as_str()),
-            ),
-            LiveState::TimedOut => unreachable!(),
-            LiveState::Unknown(state) => f.write_str(escape(format!("Unknown live state: {state}").as_str()).as_str())
+    async fn user_live_status(&self, subs: Vec<Subscription>) -> (Vec<BilibiliLive>, Vec<(Subscription, Error)>) {
+        let results: Vec<_> = stream::iter(subs)
+            .map(|sub| async move {
+                let result = self.live_status(&sub.user.id, None).await;
+                (sub, result)
+            })
+            .buffer_unordered(CONCURRENCY)
+            .collect().await;
+        let mut lives = vec![];
+        let mut errors = vec![];
+        for (sub, result) in results {
+            match result {
+                Ok(live) if matches!(live.state, LiveState::Running) => lives.push(live),
+                Ok(_) => (),
+                Err(e) => errors.push((sub, e))
+            }
         }
+        (lives, errors)
     }
 }
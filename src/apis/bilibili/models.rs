@@ -0,0 +1,98 @@
+use serde::Deserialize;
+use url::Url;
+
+/// Status shared by every response, the data is only present if the code is 0
+#[derive(Deserialize)]
+pub struct Status {
+    pub code: i64,
+    #[serde(default)]
+    pub message: String
+}
+
+#[derive(Deserialize)]
+pub struct Response<T> {
+    pub data: T
+}
+
+/// Data of the `x/web-interface/nav` endpoint, which gives the WBI key even to anonymous users
+#[derive(Deserialize)]
+pub struct Nav {
+    pub wbi_img: WbiImg
+}
+
+#[derive(Deserialize)]
+pub struct WbiImg {
+    pub img_url: String,
+    pub sub_url: String
+}
+
+/// Data of the `xlive/web-room/v1/index/getInfoByRoom` endpoint
+#[derive(Deserialize)]
+pub struct InfoByRoom {
+    pub room_info: RoomInfo,
+    pub anchor_info: AnchorInfo,
+    pub watched_show: Option<WatchedShow>
+}
+
+#[derive(Deserialize)]
+pub struct RoomInfo {
+    pub room_id: u64,
+    pub uid: u64,
+    pub title: String,
+    pub cover: Url,
+    pub live_status: u64,
+    /// Seconds since the epoch, 0 once the live has ended
+    pub live_start_time: i64
+}
+
+#[derive(Deserialize)]
+pub struct AnchorInfo {
+    pub base_info: BaseInfo
+}
+
+#[derive(Deserialize)]
+pub struct BaseInfo {
+    pub uname: String
+}
+
+#[derive(Deserialize)]
+pub struct WatchedShow {
+    pub num: u64
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::apis::error::decode;
+
+    fn fixture(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn get_info_by_room() {
+        let mut value = fixture(include_str!("../../../tests/fixtures/bilibili/get_info_by_room.json"));
+        let status: Status = decode(&value).unwrap();
+        assert_eq!(status.code, 0);
+        let info = decode::<Response<InfoByRoom>>(&value).unwrap().data;
+        assert_eq!(info.room_info.room_id, 7654321);
+        assert_eq!(info.room_info.live_status, 1);
+        assert_eq!(info.anchor_info.base_info.uname, "望远镜");
+        assert_eq!(info.watched_show.unwrap().num, 2345);
+
+        value["data"]["room_info"]["cover"] = "".into();
+        let error = decode::<Response<InfoByRoom>>(&value).err().unwrap();
+        assert_eq!(error.to_string(), "unexpected response at data.room_info.cover: relative URL without a base: \"\"");
+    }
+
+    #[test]
+    fn nav() {
+        let value = fixture(include_str!("../../../tests/fixtures/bilibili/nav.json"));
+        let status: Status = decode(&value).unwrap();
+        assert_eq!(status.code, -101);
+        let nav = decode::<Response<Nav>>(&value).unwrap().data;
+        assert!(nav.wbi_img.img_url.ends_with("/7cd084941338484aae1ad9425b84077c.png"));
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest::{header, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Failure of a request to a platform API, telling the watcher whether and when to try again
+#[derive(Clone, Debug)]
+pub enum Error {
+    /// Too many requests, until the given time if the API tells it
+    RateLimited(Option<DateTime<Utc>>),
+    /// The credentials were rejected
+    Auth(StatusCode),
+    /// No response was received, because of a network error or a timeout
+    Network(String),
+    /// Unsuccessful response
+    Http { status: StatusCode, url: String },
+    /// Error reported by the platform in the response body, e.g. the `code` of the Bilibili APIs
+    Platform { code: i64, message: String },
+    /// The user, room or Space doesn't exist
+    NotFound(String),
+    /// The response body isn't valid JSON
+    Decode(String),
+    /// The response doesn't match its model, e.g. a field is missing or has another type, the API has probably changed
+    Schema { path: String, message: String },
+    /// The request couldn't be made, e.g. the ID is invalid
+    InvalidRequest(String)
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::RateLimited(Some(reset)) => write!(f, "rate limited until {}", reset.format("%H:%M:%S UTC")),
+            Error::RateLimited(None) => f.write_str("rate limited"),
+            Error::Auth(status) => write!(f, "authentication failed ({status})"),
+            Error::Network(e) => write!(f, "network error: {e}"),
+            Error::Http { status, url } => write!(f, "{url}: {status}"),
+            Error::Platform { code, message } => write!(f, "API error {code}: {message}"),
+            Error::NotFound(name) => write!(f, "{name} not found"),
+            Error::Decode(e) => write!(f, "invalid response: {e}"),
+            Error::Schema { path, message } => write!(f, "unexpected response at {path}: {message}"),
+            Error::InvalidRequest(e) => f.write_str(e)
+        }
+    }
+}
+
+impl Error {
+    /// Time the rate limit of the response resets, from `Retry-After` in seconds or `x-rate-limit-reset`
+    pub fn rate_limit_reset(res: &Response) -> Option<DateTime<Utc>> {
+        let header = |name| res.headers().get(name)?.to_str().ok()?.parse::<i64>().ok();
+        header(header::RETRY_AFTER.as_str()).map(|seconds| Utc::now() + Duration::seconds(seconds))
+            .or_else(|| DateTime::from_timestamp(header("x-rate-limit-reset")?, 0))
+    }
+
+    /// Classify an unsuccessful response
+    pub fn from_response(res: &Response) -> Self {
+        match res.status() {
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited(Self::rate_limit_reset(res)),
+            status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => Error::Auth(status),
+            status => {
+                // the query would make every error of the same endpoint different
+                let mut url = res.url().clone();
+                url.set_query(None);
+                Error::Http { status, url: url.to_string() }
+            }
+        }
+    }
+
+    pub fn schema(path: &str, message: &str) -> Self {
+        Error::Schema { path: path.to_owned(), message: message.to_owned() }
+    }
+
+    /// Whether the error affects every request to the platform, not only the one which failed
+    pub fn is_platform_wide(&self) -> bool {
+        matches!(self, Error::RateLimited(_) | Error::Auth(_))
+    }
+
+    /// Whether the platform can't be used right now, but the request may succeed later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::RateLimited(_) | Error::Auth(_) | Error::Network(_) => true,
+            Error::Http { status, .. } => status.is_server_error() || *status == StatusCode::REQUEST_TIMEOUT,
+            _ => false
+        }
+    }
+
+    /// Whether the error hints at a change of the API, which the owner should look into
+    pub fn needs_attention(&self) -> bool {
+        !self.is_transient() && !matches!(self, Error::NotFound(_))
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(value: reqwest::Error) -> Self {
+        match value.is_decode() {
+            true => Error::Decode(value.to_string()),
+            false => Error::Network(value.to_string())
+        }
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for Error {
+    fn from(value: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        Error::Schema { path: value.path().to_string(), message: value.into_inner().to_string() }
+    }
+}
+
+/// Deserialize a response into its model, naming the path of the field which doesn't match it
+pub fn decode<T: DeserializeOwned>(value: &Value) -> Result<T, Error> {
+    Ok(serde_path_to_error::deserialize(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Room {
+        room_info: RoomInfo
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct RoomInfo {
+        room_id: u64,
+        title: String
+    }
+
+    #[test]
+    fn decode() {
+        let room: Room = super::decode(&json!({"room_info": {"room_id": 1, "title": "Live"}})).unwrap();
+        assert_eq!(room.room_info.room_id, 1);
+        let error = super::decode::<Room>(&json!({"room_info": {"room_id": "1", "title": "Live"}})).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unexpected response at room_info.room_id: invalid type: string \"1\", expected u64"
+        );
+        let error = super::decode::<Room>(&json!({"room_info": {"room_id": 1}})).unwrap_err();
+        assert_eq!(error.to_string(), "unexpected response at room_info: missing field `title`");
+    }
+}
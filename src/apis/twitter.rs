@@ -1,20 +1,40 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock}
+};
 
+use accounts::AccountPool;
 use chrono::{DateTime, Utc};
 use futures::{stream::{self}, StreamExt};
-use reqwest::header::{self, HeaderMap, HeaderValue};
+use models::{AudioSpace, AudioSpaceById, AvatarContent, LiveVideoStreamStatus, Participants, ProfileSpotlightsQuery};
+use operations::Operations;
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use strum_macros::{Display, EnumString};
-use teloxide::{types::InputFile, utils::markdown::{bold, code_block_with_lang, escape, link}};
+use teloxide::{types::InputFile, utils::markdown::{bold, escape, link}};
+use tokio::sync::Mutex;
 use url::Url;
 
-use crate::{log_utils::LogResult, platform::{Platform, User}, subscription::Subscription};
+use crate::{
+    i18n::Language,
+    log_utils::LogResult,
+    platform::{Platform, User},
+    subscription::Subscription,
+    templates::{Placeholder, Placeholders}
+};
 
-use super::{cookies::SimpleCookieJar, APIClient, LiveState, Metadata, API};
+use super::{error::{decode, Error}, fmt_duration, fmt_time, LiveState, Metadata, API, CONCURRENCY};
+
+pub mod accounts;
+mod models;
+mod operations;
 
 pub struct TwitterAPI {
-    client: APIClient
+    api_url: String,
+    /// Replaced as a whole when the credentials are reloaded, requests in flight keep the previous accounts
+    accounts: RwLock<Arc<AccountPool>>,
+    operations: Mutex<Operations>
 }
 
 #[allow(dead_code)]
@@ -27,13 +47,87 @@ pub struct TwitterSpace {
     pub creator_screen_name: String,
     pub creator_profile_image_url: Url,
     pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
     pub state: LiveState,
     pub language: String,
     pub available_for_replay: bool,
+    pub total_live_listeners: Option<u64>,
     pub master_url: Option<Url>,
+    pub participants: Vec<Participant>,
     pub metadata: Value
 }
 
+#[derive(Clone, Copy, Display)]
+pub enum ParticipantRole {
+    #[strum(to_string = "co-host")]
+    CoHost,
+    #[strum(to_string = "speaker")]
+    Speaker
+}
+
+pub struct Participant {
+    pub id: String,
+    pub name: String,
+    pub screen_name: String,
+    pub role: ParticipantRole
+}
+
+/// A subscribed user taking part in someone else's Space
+pub struct Appearance<'a> {
+    pub participant: &'a Participant,
+    pub space: &'a TwitterSpace
+}
+
+impl TwitterSpace {
+    fn parse_participants(participants: Participants) -> Vec<Participant> {
+        let roles = [(ParticipantRole::CoHost, participants.admins), (ParticipantRole::Speaker, participants.speakers)];
+        roles.into_iter().flat_map(|(role, participants)| {
+            participants.into_iter().filter_map(move |p| Some(Participant {
+                id: p.user_results?.rest_id,
+                name: p.display_name,
+                screen_name: p.twitter_screen_name,
+                role
+            }))
+        }).collect()
+    }
+
+    fn parse(
+        live_id: &str, language: Option<String>, master_url: Option<Url>, space: AudioSpace, raw: Value
+    ) -> Result<Self, Error> {
+        let metadata = space.metadata.ok_or_else(|| Error::NotFound(format!("Space {live_id}")))?;
+        let creator = metadata.creator_results.result;
+        Ok(TwitterSpace {
+            id: live_id.to_owned(),
+            url: format!("https://twitter.com/i/spaces/{live_id}").parse()
+                .map_err(|e: url::ParseError| Error::InvalidRequest(format!("Invalid Space ID {live_id}: {e}")))?,
+            title: metadata.title,
+            creator_name: creator.legacy.name,
+            creator_id: creator.rest_id,
+            creator_screen_name: creator.legacy.screen_name,
+            creator_profile_image_url: creator.legacy.profile_image_url_https,
+            start_time: DateTime::from_timestamp_millis(metadata.started_at)
+                .ok_or_else(|| Error::schema("data.audioSpace.metadata.started_at", "timestamp out of range"))?,
+            end_time: metadata.ended_at.and_then(DateTime::from_timestamp_millis),
+            state: metadata.state.parse().unwrap_or(LiveState::Unknown(metadata.state)),
+            language: language.unwrap_or("und".to_owned()),
+            available_for_replay: metadata.is_space_available_for_replay,
+            total_live_listeners: metadata.total_live_listeners,
+            master_url,
+            participants: TwitterSpace::parse_participants(space.participants),
+            metadata: raw
+        })
+    }
+
+    /// Find the appearance of the user in this Space, unless the user is the creator.
+    pub fn appearance(&self, user_id: &str) -> Option<Appearance<'_>> {
+        if self.creator_id == user_id {
+            return None;
+        }
+        let participant = self.participants.iter().find(|p| p.id == user_id)?;
+        Some(Appearance { participant, space: self })
+    }
+}
+
 impl Metadata for TwitterSpace {
     type Id = String;
 
@@ -45,6 +139,26 @@ impl Metadata for TwitterSpace {
         &self.state
     }
 
+    fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    fn get_url(&self) -> &Url {
+        &self.url
+    }
+
+    fn get_cover(&self) -> Option<&Url> {
+        None
+    }
+
+    fn get_start_time(&self) -> Option<DateTime<Utc>> {
+        Some(self.start_time)
+    }
+
+    fn set_start_time(&mut self, start_time: DateTime<Utc>) {
+        self.start_time = start_time;
+    }
+
     fn get_attachment(&self) -> InputFile {
         InputFile::memory(self.metadata.to_string()).file_name(format!("{}.json", self.id))
     }
@@ -55,6 +169,31 @@ impl Metadata for TwitterSpace {
             user: User { id: self.creator_id.clone(), username: self.creator_screen_name.clone() }
         }
     }
+
+    fn placeholders(&self) -> Placeholders {
+        let mut values = Placeholders::from([
+            (Placeholder::Platform, Platform::TwitterSpace.to_string()),
+            (Placeholder::Creator, self.creator_name.clone()),
+            (Placeholder::Handle, format!("@{}", self.creator_screen_name)),
+            (Placeholder::ProfileUrl, format!("https://twitter.com/{}", self.creator_screen_name)),
+            (Placeholder::Title, self.title.clone()),
+            (Placeholder::Url, self.url.to_string()),
+            (Placeholder::StartTime, fmt_time(self.start_time))
+        ]);
+        if let Some(master_url) = &self.master_url {
+            values.insert(Placeholder::MasterUrl, master_url.to_string());
+        }
+        if let Some(listeners) = self.total_live_listeners {
+            values.insert(Placeholder::Audience, listeners.to_string());
+        }
+        if matches!(self.state, LiveState::Ended | LiveState::TimedOut) {
+            values.insert(Placeholder::Duration, fmt_duration(self.end_time.unwrap_or(Utc::now()) - self.start_time));
+            if self.available_for_replay {
+                values.insert(Placeholder::ReplayUrl, self.url.to_string());
+            }
+        }
+        values
+    }
 }
 
 #[derive(Display, EnumString)]
@@ -84,148 +223,170 @@ struct AudioSpaceByIdVariables<'a> {
 }
 
 impl TwitterAPI {
-    pub fn new(auth_token: &str, csrf_token: &str) -> Self {
-        let mut headers = HeaderMap::new();
-        headers.append(
-            header::AUTHORIZATION,
-            HeaderValue::from_static(
-                "Bearer AAAAAAAAAAAAAAAAAAAAANRILgAAAAAAnNwIzUejRCOuH5E6I8xnZz4puTs%3D1Zv7ttfk8LF81IUq16cHjhLTvJu4FA33AGWWjCpTnA"
-            )
-        );
-        headers.append("x-csrf-token", HeaderValue::from_str(csrf_token).expect("Invalid x-csrf-token"));
-        let cookies = SimpleCookieJar::default();
-        cookies.add_cookie("auth_token", auth_token);
-        cookies.add_cookie("ct0", csrf_token);
-        Self { client: APIClient::new("https://x.com/i/api/", headers, Some(cookies)) }
+    /// Client of the API at `api_url`, e.g. `https://x.com/i/api/`, discovering the GraphQL operations from the web
+    /// client at `web_url`, e.g. `https://x.com/`
+    pub fn new(api_url: &str, web_url: &str, credentials: &[(String, String)]) -> Self {
+        Self {
+            api_url: api_url.to_owned(),
+            accounts: RwLock::new(AccountPool::new(api_url, credentials).into()),
+            operations: Operations::new(web_url).into()
+        }
+    }
+
+    pub fn accounts(&self) -> Arc<AccountPool> {
+        self.accounts.read().expect("failed to lock accounts").clone()
+    }
+
+    /// Use new accounts from now on, resetting the status of the previous ones
+    pub fn set_credentials(&self, credentials: &[(String, String)]) {
+        *self.accounts.write().expect("failed to lock accounts") = AccountPool::new(&self.api_url, credentials).into();
+    }
+
+    async fn get<T: Serialize>(&self, path: &[&str], params: Option<&T>) -> Result<Value, Error> {
+        let res = self.accounts().send(path, params).await?;
+        if !res.status().is_success() {
+            return Err(Error::from_response(&res));
+        }
+        Ok(res.json().await?)
+    }
+
+    async fn graphql<T: Serialize>(&self, operation_name: &str, variables: &T) -> Result<Value, Error> {
+        let variables = serde_json::to_string(variables).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+        for retry in [true, false] {
+            let operation = self.operations.lock().await.get(operation_name).await
+                .ok_or_else(|| Error::InvalidRequest(format!("Unknown GraphQL operation {operation_name}")))?;
+            let mut params = HashMap::from([("variables", variables.clone())]);
+            if let Some(features) = operation.features_json() {
+                params.insert("features", features);
+            }
+            let res = self.accounts().send(
+                &[&Endpoint::GraphQL.to_string(), &operation.query_id, operation_name], Some(&params)
+            ).await?;
+            match res.status() {
+                status if status.is_success() => {
+                    let result: Value = res.json().await?;
+                    // errors are reported with a successful status when the query can't be answered at all
+                    return match &result["errors"][0] {
+                        Value::Object(error) if result["data"].as_object().is_none_or(Map::is_empty) => {
+                            Err(Error::Platform {
+                                code: error.get("code").and_then(Value::as_i64).unwrap_or_default(),
+                                message: error.get("message").and_then(Value::as_str).unwrap_or_default().to_owned()
+                            })
+                        }
+                        _ => Ok(result)
+                    };
+                }
+                StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND if retry => {
+                    log::warn!("{operation_name} returned {}, refreshing GraphQL operations", res.status());
+                    self.operations.lock().await.refresh().await;
+                }
+                _ => return Err(Error::from_response(&res))
+            }
+        }
+        unreachable!("the last attempt always returns")
     }
 
-    async fn audio_space_by_id(&self, space_id: &str) -> Option<Value> {
-        let query_id = "xVEzTKg_mLTHubK5ayL0HA";
-        let operation_name = "AudioSpaceById";
+    async fn audio_space_by_id(&self, space_id: &str) -> Result<Value, Error> {
         let variables = AudioSpaceByIdVariables {
             id: space_id,
             is_metatags_query: true,
             with_replays: true,
             with_listeners: true
         };
-        let features = "{\"spaces_2022_h2_clipping\":true,\"spaces_2022_h2_spaces_communities\":true,\"responsive_web_graphql_exclude_directive_enabled\":true,\"verified_phone_label_enabled\":false,\"creator_subscriptions_tweet_preview_api_enabled\":true,\"responsive_web_graphql_skip_user_profile_image_extensions_enabled\":false,\"tweetypie_unmention_optimization_enabled\":true,\"responsive_web_edit_tweet_api_enabled\":true,\"graphql_is_translatable_rweb_tweet_is_translatable_enabled\":true,\"view_counts_everywhere_api_enabled\":true,\"longform_notetweets_consumption_enabled\":true,\"responsive_web_twitter_article_tweet_consumption_enabled\":false,\"tweet_awards_web_tipping_enabled\":false,\"freedom_of_speech_not_reach_fetch_enabled\":true,\"standardized_nudges_misinfo\":true,\"tweet_with_visibility_results_prefer_gql_limited_actions_policy_enabled\":true,\"responsive_web_graphql_timeline_navigation_enabled\":true,\"longform_notetweets_rich_text_read_enabled\":true,\"longform_notetweets_inline_media_enabled\":true,\"responsive_web_media_download_video_enabled\":false,\"responsive_web_enhance_cards_enabled\":false}";
-        let params = HashMap::from([
-            ("variables", serde_json::to_string(&variables).ok()?),
-            ("features", features.to_owned())
-        ]);
-        self.client.get(&[&Endpoint::GraphQL.to_string(), query_id, operation_name], Some(params)).await
+        self.graphql("AudioSpaceById", &variables).await
     }
 
-    async fn profile_spotlights_query(&self, screen_name: &str) -> Option<Value> {
-        let query_id = "ZQEuHPrIYlvh1NAyIQHP_w";
-        let operation_name = "ProfileSpotlightsQuery";
-        let variables = ProfileSpotlightsQueryVariables { screen_name };
-        let params = HashMap::from([
-            ("variables", serde_json::to_string(&variables).ok()?)
-        ]);
-        self.client.get(&[&Endpoint::GraphQL.to_string(), query_id, operation_name], Some(params)).await
+    async fn profile_spotlights_query(&self, screen_name: &str) -> Result<ProfileSpotlightsQuery, Error> {
+        decode(&self.graphql("ProfileSpotlightsQuery", &ProfileSpotlightsQueryVariables { screen_name }).await?)
     }
 
-    async fn avatar_content(&self, user_ids: &[&str]) -> Option<Value> {
+    async fn avatar_content(&self, user_ids: &[&str]) -> Result<AvatarContent, Error> {
         let version = "v1";
         let endpoint = "avatar_content";
         let params = HashMap::from([
             ("user_ids", user_ids.join(",")),
             ("only_spaces", true.to_string())
         ]);
-        self.client.get(&[&Endpoint::Fleets.to_string(), version, endpoint], Some(params)).await
+        decode(&self.get(&[&Endpoint::Fleets.to_string(), version, endpoint], Some(&params)).await?)
     }
 
-    async fn status(&self, media_key: &str) -> Option<Value> {
-        self.client.get::<()>(&[&Endpoint::LiveVideoStream.to_string(), "status", media_key], None).await
+    async fn status(&self, media_key: &str) -> Result<LiveVideoStreamStatus, Error> {
+        decode(&self.get::<()>(&[&Endpoint::LiveVideoStream.to_string(), "status", media_key], None).await?)
     }
 
-    pub async fn user_id(&self, screen_name: &str) -> Option<String> {
+    pub async fn user_id(&self, screen_name: &str) -> Result<String, Error> {
         let result = self.profile_spotlights_query(screen_name).await?;
-        let value = &result["data"]["user_result_by_screen_name"]["result"]["rest_id"];
-        Some(value.as_str()?.to_string())
+        // the user result is empty if no user has this screen name
+        let user = result.data.user_result_by_screen_name.and_then(|user| user.result);
+        Ok(user.ok_or_else(|| Error::NotFound(format!("@{screen_name}")))?.rest_id)
     }
 }
 
 impl API<TwitterSpace> for TwitterAPI {
-    async fn live_status(&self, live_id: &String, language: Option<String>) -> Option<TwitterSpace> {
-        let space = self.audio_space_by_id(live_id).await?;
-        let metadata = space["data"]["audioSpace"]["metadata"].as_object()?;
-        let state = metadata["state"].as_str()?.parse().ok()?;
-        let master_url = if let LiveState::Running = state {
-            let live_status = self.status(metadata["media_key"].as_str()?).await?;
-            Some(live_status["source"]["location"].as_str()?
-                .replace("dynamic_playlist.m3u8?type=live", "master_playlist.m3u8").parse().log_ok("Twitter Space Playlist URL")?)
-        } else {
-            None
+    async fn live_status(&self, live_id: &String, language: Option<String>) -> Result<TwitterSpace, Error> {
+        let raw = self.audio_space_by_id(live_id).await?;
+        let space = decode::<AudioSpaceById>(&raw)?.data.audio_space;
+        let master_url = match space.metadata.as_ref().map(|metadata| (metadata.state.as_str(), &metadata.media_key)) {
+            Some(("Running", Some(media_key))) => {
+                let location = self.status(media_key).await?.source.location.to_string();
+                location.replace("dynamic_playlist.m3u8?type=live", "master_playlist.m3u8").parse()
+                    .log_ok("Twitter Space Playlist URL")
+            }
+            _ => None
         };
-        Some(TwitterSpace {
-            id: live_id.clone(),
-            url: format!("https://twitter.com/i/spaces/{live_id}").parse().log_ok("Twitter Space URL")?,
-            title: metadata["title"].as_str()?.to_owned(),
-            creator_name: metadata["creator_results"]["result"]["legacy"]["name"].as_str()?.to_owned(),
-            creator_id: metadata["creator_results"]["result"]["rest_id"].as_str()?.to_owned(),
-            creator_screen_name: metadata["creator_results"]["result"]["legacy"]["screen_name"].as_str()?.to_owned(),
-            creator_profile_image_url:
-                metadata["creator_results"]["result"]["legacy"]["profile_image_url_https"].as_str()?.parse().log_ok("Twitter Profile Image URL")?,
-            start_time: DateTime::from_timestamp_millis(metadata["started_at"].as_i64()?)?,
-            state,
-            language: language.unwrap_or("und".to_owned()),
-            available_for_replay: metadata["is_space_available_for_replay"].as_bool()?,
-            master_url,
-            metadata: space
-        })
+        TwitterSpace::parse(live_id, language, master_url, space, raw)
     }
 
-    async fn user_live_status(&self, subs: Vec<Subscription>) -> Vec<TwitterSpace> {
-        let mut spaces = vec![];
-        for user_ids in subs.iter().map(|sub| sub.user.id.as_str()).collect::<Vec<&str>>().chunks(100) {
-            if let Some(result) = self.avatar_content(user_ids).await {
-                if let Some(users) = result["users"].as_object().map(|o| stream::iter(o.values())) {
-                    spaces.extend(
-                        users.filter_map(async |value| {
-                            let audio_space = &value["spaces"]["live_content"]["audiospace"].as_object()?;
-                            self.live_status(
-                                &audio_space.get("broadcast_id")?.as_str()?.to_owned(),
-                                Some(audio_space.get("language")?.as_str()?.to_owned())
-                            ).await.filter(|space| matches!(space.state, LiveState::Running))
-                        }).collect::<Vec<_>>().await
-                    );
+    async fn user_live_status(&self, subs: Vec<Subscription>) -> (Vec<TwitterSpace>, Vec<(Subscription, Error)>) {
+        let mut broadcasts = vec![];
+        let mut errors = vec![];
+        let mut seen = HashSet::new();
+        for chunk in subs.chunks(100) {
+            let user_ids: Vec<_> = chunk.iter().map(|sub| sub.user.id.as_str()).collect();
+            let users = match self.avatar_content(&user_ids).await {
+                Ok(content) => content.users,
+                Err(e) => {
+                    errors.extend(chunk.iter().map(|sub| (sub.clone(), e.clone())));
+                    continue;
                 }
+            };
+            broadcasts.extend(users.into_iter().filter_map(|(user_id, user)| {
+                let space = user.spaces.live_content?.audiospace;
+                let sub = chunk.iter().find(|sub| sub.user.id == user_id).cloned();
+                // several subscribed users can be in the same Space
+                seen.insert(space.broadcast_id.clone()).then_some((sub, space.broadcast_id, space.language))
+            }));
+        }
+        let results: Vec<_> = stream::iter(broadcasts)
+            .map(|(sub, id, language)| async move { (sub, self.live_status(&id, language).await) })
+            .buffer_unordered(CONCURRENCY)
+            .collect().await;
+        let mut spaces = vec![];
+        for (sub, result) in results {
+            match (result, sub) {
+                (Ok(space), _) if matches!(space.state, LiveState::Running) => spaces.push(space),
+                (Ok(_), _) => (),
+                (Err(e), Some(sub)) => errors.push((sub, e)),
+                (Err(e), None) => log::error!("Failed to get Twitter Space: {e}")
             }
         }
-        spaces
+        (spaces, errors)
     }
 }
 
-impl Display for TwitterSpace {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.state {
-            LiveState::Running => write!(
-                f,
-                "{} \\({}\\)'s Twitter Space started\n{}\n{}",
-                bold(escape(self.creator_name.as_str()).as_str()),
-                link(
-                    format!("https://twitter.com/{}", escape(self.creator_screen_name.as_str())).as_str(),
-                    format!("@{}", escape(self.creator_screen_name.as_str())).as_str()
-                ),
-                link(self.url.as_str(), escape(self.title.as_str()).as_str()),
-                code_block_with_lang(
-                    format!(
-                        "twspace_dl -ei {}{}", self.url, self.master_url.as_ref().map(|s| format!(" -f {s}")).unwrap_or_default()
-                    ).as_str(), "shell"
-                )
-            ),
-            LiveState::Ended | LiveState::TimedOut => write!(
-                f,
-                "{} \\({}\\)'s Twitter Space ended",
-                bold(escape(self.creator_name.as_str()).as_str()),
-                link(
-                    format!("https://twitter.com/{}", escape(self.creator_screen_name.as_str())).as_str(),
-                    format!("@{}", escape(self.creator_screen_name.as_str())).as_str()
-                )
-            ),
-            LiveState::Unknown(state) => f.write_str(escape(format!("Unknown live state: {state}").as_str()).as_str())
-        }
+impl Appearance<'_> {
+    pub fn message(&self, language: Language) -> String {
+        let handle = |screen_name: &str| link(
+            format!("https://twitter.com/{}", escape(screen_name)).as_str(),
+            format!("@{}", escape(screen_name)).as_str()
+        );
+        language.markdown("appearance", &[
+            ("participant", &bold(escape(self.participant.name.as_str()).as_str())),
+            ("participant_handle", &handle(&self.participant.screen_name)),
+            ("creator", &bold(escape(self.space.creator_name.as_str()).as_str())),
+            ("creator_handle", &handle(&self.space.creator_screen_name)),
+            ("role", &escape(language.get(&format!("role.{}", self.participant.role)))),
+            ("space", &link(self.space.url.as_str(), escape(self.space.title.as_str()).as_str()))
+        ])
     }
 }
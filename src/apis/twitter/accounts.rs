@@ -0,0 +1,256 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        Mutex
+    }
+};
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest::{
+    header::{self, HeaderMap, HeaderValue},
+    Response, StatusCode
+};
+use serde::Serialize;
+
+use crate::{
+    apis::{cookies::SimpleCookieJar, error::Error, APIClient},
+    platform::Platform
+};
+
+#[derive(Clone)]
+pub enum AccountStatus {
+    Active,
+    RateLimited(DateTime<Utc>),
+    /// Rejected with the status, tried again after the given time
+    Disabled(StatusCode, DateTime<Utc>)
+}
+
+impl Display for AccountStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountStatus::Active => f.write_str("active"),
+            AccountStatus::RateLimited(reset) => write!(f, "rate limited until {}", reset.format("%H:%M:%S UTC")),
+            AccountStatus::Disabled(status, retry) => {
+                write!(f, "disabled ({status}) until {}", retry.format("%Y-%m-%d %H:%M:%S UTC"))
+            }
+        }
+    }
+}
+
+struct Account {
+    client: APIClient,
+    status: Mutex<AccountStatus>,
+    /// Rejections in a row, which double the time the account is disabled
+    rejections: AtomicU32
+}
+
+impl Account {
+    const BEARER_TOKEN: &str =
+        "Bearer AAAAAAAAAAAAAAAAAAAAANRILgAAAAAAnNwIzUejRCOuH5E6I8xnZz4puTs%3D1Zv7ttfk8LF81IUq16cHjhLTvJu4FA33AGWWjCpTnA";
+    /// Used when a 429 response doesn't tell us when the limit resets
+    const DEFAULT_COOLDOWN: Duration = Duration::minutes(15);
+    /// Time a rejected account is disabled for the first time, doubled after each rejection in a row up to a day,
+    /// since Twitter also rejects accounts temporarily, e.g. until a challenge is solved
+    const DISABLED_COOLDOWN: Duration = Duration::hours(1);
+
+    fn new(base_url: &str, auth_token: &str, csrf_token: &str) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.append(header::AUTHORIZATION, HeaderValue::from_static(Self::BEARER_TOKEN));
+        headers.append("x-csrf-token", HeaderValue::from_str(csrf_token).expect("Invalid x-csrf-token"));
+        let cookies = SimpleCookieJar::default();
+        cookies.add_cookie("auth_token", auth_token);
+        cookies.add_cookie("ct0", csrf_token);
+        Self {
+            client: APIClient::new(Platform::TwitterSpace, base_url, headers, Some(cookies)),
+            status: AccountStatus::Active.into(),
+            rejections: AtomicU32::new(0)
+        }
+    }
+
+    fn status(&self) -> AccountStatus {
+        let mut status = self.status.lock().expect("failed to lock account status");
+        if let AccountStatus::RateLimited(reset) | AccountStatus::Disabled(_, reset) = *status {
+            if reset <= Utc::now() {
+                *status = AccountStatus::Active;
+            }
+        }
+        status.clone()
+    }
+
+    /// Disable the account after a rejection, returning whether it had been accepted since the previous one
+    fn reject(&self, status: StatusCode) -> bool {
+        let rejections = self.rejections.fetch_add(1, Ordering::Relaxed);
+        let cooldown = (Self::DISABLED_COOLDOWN * 2i32.pow(rejections.min(5))).min(Duration::days(1));
+        self.set_status(AccountStatus::Disabled(status, Utc::now() + cooldown));
+        rejections == 0
+    }
+
+    fn set_status(&self, status: AccountStatus) {
+        *self.status.lock().expect("failed to lock account status") = status;
+    }
+}
+
+/// Twitter accounts used in rotation, so a rate-limited or locked account doesn't stop polling
+pub struct AccountPool {
+    accounts: Vec<Account>,
+    next: AtomicUsize,
+    exhausted: AtomicBool,
+    alerts: Mutex<Vec<String>>
+}
+
+impl AccountPool {
+    pub fn new(base_url: &str, credentials: &[(String, String)]) -> Self {
+        assert!(!credentials.is_empty(), "No Twitter account configured");
+        Self {
+            accounts: credentials.iter().map(|(auth_token, csrf_token)| Account::new(base_url, auth_token, csrf_token)).collect(),
+            next: AtomicUsize::new(0),
+            exhausted: AtomicBool::new(false),
+            alerts: Mutex::new(vec![])
+        }
+    }
+
+    fn alert(&self, msg: String) {
+        log::warn!("{msg}");
+        self.alerts.lock().expect("failed to lock account pool alerts").push(msg);
+    }
+
+    /// Take the health alerts raised since the last call, to be reported to the bot owner.
+    pub fn take_alerts(&self) -> Vec<String> {
+        std::mem::take(&mut *self.alerts.lock().expect("failed to lock account pool alerts"))
+    }
+
+    /// Send the request with the next available account, moving on to the following account
+    /// whenever the current one is rate limited, rejected or its request fails.
+    pub async fn send<T: Serialize>(&self, path: &[&str], params: Option<&T>) -> Result<Response, Error> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut network_error = None;
+        for i in 0..self.accounts.len() {
+            let index = (start + i) % self.accounts.len();
+            let account = &self.accounts[index];
+            if !matches!(account.status(), AccountStatus::Active) {
+                continue;
+            }
+            let res = match account.client.send(path, params).await {
+                Ok(res) => res,
+                // e.g. a connection reset or a timeout, which another account may not run into
+                Err(e @ Error::Network(_)) => {
+                    log::warn!("Request with Twitter account #{} failed: {e}", index + 1);
+                    network_error = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e)
+            };
+            match res.status() {
+                StatusCode::TOO_MANY_REQUESTS => {
+                    let reset = Error::rate_limit_reset(&res).unwrap_or(Utc::now() + Account::DEFAULT_COOLDOWN);
+                    account.set_status(AccountStatus::RateLimited(reset));
+                    log::warn!("Twitter account #{} is {}", index + 1, account.status());
+                }
+                status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                    if account.reject(status) {
+                        self.alert(format!("Twitter account #{} has been {}", index + 1, account.status()));
+                    } else {
+                        log::warn!("Twitter account #{} is {}", index + 1, account.status());
+                    }
+                }
+                _ => {
+                    if res.headers().get("x-rate-limit-remaining").is_some_and(|v| v == "0") {
+                        if let Some(reset) = Error::rate_limit_reset(&res) {
+                            account.set_status(AccountStatus::RateLimited(reset));
+                        }
+                    }
+                    account.rejections.store(0, Ordering::Relaxed);
+                    self.exhausted.store(false, Ordering::Relaxed);
+                    return Ok(res);
+                }
+            }
+        }
+        // the network is more likely at fault than the accounts
+        if let Some(e) = network_error {
+            return Err(e);
+        }
+        if !self.exhausted.swap(true, Ordering::Relaxed) {
+            self.alert("No Twitter account is available, polling is suspended".to_owned());
+        }
+        let reset = self.accounts.iter().filter_map(|account| match account.status() {
+            AccountStatus::RateLimited(reset) => Some(reset),
+            _ => None
+        }).min();
+        Err(match reset {
+            Some(reset) => Error::RateLimited(Some(reset)),
+            None => Error::Auth(StatusCode::UNAUTHORIZED)
+        })
+    }
+}
+
+impl AccountPool {
+    /// Number of accounts which are neither rate limited nor disabled
+    pub fn active(&self) -> usize {
+        self.accounts.iter().filter(|a| matches!(a.status(), AccountStatus::Active)).count()
+    }
+
+    /// Use the disabled accounts again without waiting for their cooldown, returning how many there were
+    pub fn enable(&self) -> usize {
+        let disabled: Vec<_> = self.accounts.iter()
+            .filter(|account| matches!(account.status(), AccountStatus::Disabled(..))).collect();
+        for account in &disabled {
+            account.rejections.store(0, Ordering::Relaxed);
+            account.set_status(AccountStatus::Active);
+        }
+        disabled.len()
+    }
+}
+
+impl Display for AccountPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let active = self.active();
+        write!(f, "Twitter accounts: {active}/{} active", self.accounts.len())?;
+        for (i, account) in self.accounts.iter().enumerate() {
+            write!(f, "\n#{}: {}", i + 1, account.status())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{header, method},
+        Mock,
+        MockServer,
+        ResponseTemplate
+    };
+
+    use super::*;
+
+    fn credentials() -> Vec<(String, String)> {
+        vec![("auth_a".to_owned(), "a".to_owned()), ("auth_b".to_owned(), "b".to_owned())]
+    }
+
+    #[tokio::test]
+    async fn disables_rejected_accounts() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).and(header("x-csrf-token", "a"))
+            .respond_with(ResponseTemplate::new(401)).mount(&server).await;
+        Mock::given(method("GET")).respond_with(ResponseTemplate::new(200)).mount(&server).await;
+        let pool = AccountPool::new(&format!("{}/", server.uri()), &credentials());
+        for _ in 0..3 {
+            assert_eq!(pool.send::<()>(&["test"], None).await.unwrap().status(), StatusCode::OK);
+        }
+        assert_eq!(pool.active(), 1);
+        assert!(matches!(pool.accounts[0].status(), AccountStatus::Disabled(StatusCode::UNAUTHORIZED, _)));
+        assert_eq!(pool.take_alerts().len(), 1);
+        assert_eq!(pool.enable(), 1);
+        assert_eq!(pool.active(), 2);
+    }
+
+    #[tokio::test]
+    async fn network_errors() {
+        let pool = AccountPool::new("http://127.0.0.1:1/", &credentials());
+        assert!(matches!(pool.send::<()>(&["test"], None).await, Err(Error::Network(_))));
+        // the accounts are not blamed for the network
+        assert_eq!(pool.active(), 2);
+        assert!(pool.take_alerts().is_empty());
+    }
+}
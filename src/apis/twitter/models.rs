@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer};
+use url::Url;
+
+/// Response of the `AudioSpaceById` GraphQL query
+#[derive(Deserialize)]
+pub struct AudioSpaceById {
+    pub data: AudioSpaceData
+}
+
+#[derive(Deserialize)]
+pub struct AudioSpaceData {
+    #[serde(rename = "audioSpace")]
+    pub audio_space: AudioSpace
+}
+
+#[derive(Deserialize)]
+pub struct AudioSpace {
+    /// Missing if the Space doesn't exist
+    pub metadata: Option<SpaceMetadata>,
+    #[serde(default)]
+    pub participants: Participants
+}
+
+#[derive(Deserialize)]
+pub struct SpaceMetadata {
+    pub state: String,
+    pub title: String,
+    pub media_key: Option<String>,
+    /// Milliseconds since the epoch
+    pub started_at: i64,
+    /// Milliseconds since the epoch, given as a string
+    #[serde(default, deserialize_with = "millis")]
+    pub ended_at: Option<i64>,
+    pub is_space_available_for_replay: bool,
+    pub total_live_listeners: Option<u64>,
+    pub creator_results: CreatorResults
+}
+
+#[derive(Deserialize)]
+pub struct CreatorResults {
+    pub result: Creator
+}
+
+#[derive(Deserialize)]
+pub struct Creator {
+    pub rest_id: String,
+    pub legacy: CreatorProfile
+}
+
+#[derive(Deserialize)]
+pub struct CreatorProfile {
+    pub name: String,
+    pub screen_name: String,
+    pub profile_image_url_https: Url
+}
+
+#[derive(Default, Deserialize)]
+pub struct Participants {
+    #[serde(default)]
+    pub admins: Vec<SpaceParticipant>,
+    #[serde(default)]
+    pub speakers: Vec<SpaceParticipant>
+}
+
+#[derive(Deserialize)]
+pub struct SpaceParticipant {
+    pub display_name: String,
+    pub twitter_screen_name: String,
+    /// Missing for participants without a Twitter account
+    pub user_results: Option<UserId>
+}
+
+#[derive(Deserialize)]
+pub struct UserId {
+    pub rest_id: String
+}
+
+/// Response of the `ProfileSpotlightsQuery` GraphQL query
+#[derive(Deserialize)]
+pub struct ProfileSpotlightsQuery {
+    pub data: ProfileSpotlightsData
+}
+
+#[derive(Deserialize)]
+pub struct ProfileSpotlightsData {
+    pub user_result_by_screen_name: Option<UserResult>
+}
+
+#[derive(Deserialize)]
+pub struct UserResult {
+    /// Missing if no user has the screen name
+    pub result: Option<UserId>
+}
+
+/// Response of the `fleets/v1/avatar_content` endpoint
+#[derive(Deserialize)]
+pub struct AvatarContent {
+    #[serde(default)]
+    pub users: HashMap<String, AvatarUser>
+}
+
+#[derive(Deserialize)]
+pub struct AvatarUser {
+    #[serde(default)]
+    pub spaces: AvatarSpaces
+}
+
+#[derive(Default, Deserialize)]
+pub struct AvatarSpaces {
+    /// Missing if the user isn't in a Space
+    pub live_content: Option<LiveContent>
+}
+
+#[derive(Deserialize)]
+pub struct LiveContent {
+    pub audiospace: BroadcastSummary
+}
+
+#[derive(Deserialize)]
+pub struct BroadcastSummary {
+    pub broadcast_id: String,
+    pub language: Option<String>
+}
+
+/// Response of the `1.1/live_video_stream/status` endpoint
+#[derive(Deserialize)]
+pub struct LiveVideoStreamStatus {
+    pub source: StreamSource
+}
+
+#[derive(Deserialize)]
+pub struct StreamSource {
+    pub location: Url
+}
+
+/// Timestamp given either as a number or as a string
+fn millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Millis {
+        Number(i64),
+        String(String)
+    }
+
+    match Option::<Millis>::deserialize(deserializer)? {
+        Some(Millis::Number(millis)) => Ok(Some(millis)),
+        Some(Millis::String(millis)) => millis.parse().map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::apis::error::decode;
+
+    fn fixture(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn audio_space_by_id() {
+        let mut value = fixture(include_str!("../../../tests/fixtures/twitter/audio_space_by_id.json"));
+        let space: AudioSpaceById = decode(&value).unwrap();
+        let metadata = space.data.audio_space.metadata.unwrap();
+        assert_eq!(metadata.state, "Running");
+        assert_eq!(metadata.title, "Morning chat");
+        assert_eq!(metadata.started_at, 1735700400000);
+        assert_eq!(metadata.ended_at, None);
+        assert_eq!(metadata.total_live_listeners, Some(42));
+        assert_eq!(metadata.creator_results.result.legacy.screen_name, "telescope_dev");
+        let participants = space.data.audio_space.participants;
+        assert_eq!(participants.admins.len(), 2);
+        assert_eq!(participants.speakers[0].user_results.as_ref().unwrap().rest_id, "555666777");
+
+        value["data"]["audioSpace"]["metadata"]["ended_at"] = json!("1735710000000");
+        let space: AudioSpaceById = decode(&value).unwrap();
+        assert_eq!(space.data.audio_space.metadata.unwrap().ended_at, Some(1735710000000));
+
+        value["data"]["audioSpace"]["metadata"]["creator_results"]["result"]["legacy"]
+            .as_object_mut().unwrap().remove("screen_name");
+        let error = decode::<AudioSpaceById>(&value).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "unexpected response at data.audioSpace.metadata.creator_results.result.legacy: missing field `screen_name`"
+        );
+    }
+
+    #[test]
+    fn profile_spotlights_query() {
+        let value = fixture(include_str!("../../../tests/fixtures/twitter/profile_spotlights_query.json"));
+        let user: ProfileSpotlightsQuery = decode(&value).unwrap();
+        assert_eq!(user.data.user_result_by_screen_name.unwrap().result.unwrap().rest_id, "123456789");
+        let user: ProfileSpotlightsQuery = decode(&json!({"data": {"user_result_by_screen_name": {}}})).unwrap();
+        assert!(user.data.user_result_by_screen_name.unwrap().result.is_none());
+    }
+
+    #[test]
+    fn avatar_content() {
+        let value = fixture(include_str!("../../../tests/fixtures/twitter/avatar_content.json"));
+        let content: AvatarContent = decode(&value).unwrap();
+        let space = content.users["123456789"].spaces.live_content.as_ref().unwrap();
+        assert_eq!(space.audiospace.broadcast_id, "1YqKDqDXAbwKV");
+        assert_eq!(space.audiospace.language.as_deref(), Some("en"));
+        assert!(content.users["555666777"].spaces.live_content.is_none());
+    }
+
+    #[test]
+    fn live_video_stream_status() {
+        let value = fixture(include_str!("../../../tests/fixtures/twitter/live_video_stream_status.json"));
+        let status: LiveVideoStreamStatus = decode(&value).unwrap();
+        assert!(status.source.location.path().ends_with("/dynamic_playlist.m3u8"));
+    }
+}
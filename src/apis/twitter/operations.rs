@@ -0,0 +1,145 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, Utc};
+use lazy_regex::{lazy_regex, Lazy};
+use regex::Regex;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+
+use crate::{apis::APIClient, log_utils::LogResult, platform::Platform};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operation {
+    pub query_id: String,
+    pub features: BTreeMap<String, bool>
+}
+
+impl Operation {
+    pub fn features_json(&self) -> Option<String> {
+        (!self.features.is_empty()).then(|| serde_json::to_string(&self.features).ok()).flatten()
+    }
+}
+
+/// GraphQL query IDs and feature flags of the web client, scraped from its JS bundle.
+pub struct Operations {
+    client: APIClient,
+    update_time: Option<DateTime<Utc>>,
+    operations: HashMap<String, Operation>
+}
+
+impl Operations {
+    const BUNDLE_REGEX: Lazy<Regex> =
+        lazy_regex!(r#"https://abs\.twimg\.com/responsive-web/client-web(?:-legacy)?/main\.\w+\.js"#);
+    const OPERATION_REGEX: Lazy<Regex> = lazy_regex!(
+        r#"queryId:"(?P<query_id>[\w-]+)",operationName:"(?P<name>\w+)",operationType:"\w+",metadata:\{featureSwitches:\[(?P<features>[^\]]*)\]"#
+    );
+    const FEATURE_NAME_REGEX: Lazy<Regex> = lazy_regex!(r#""(\w+)""#);
+    const FEATURE_VALUE_REGEX: Lazy<Regex> = lazy_regex!(r#""(\w+)":\{"value":(true|false)\}"#);
+    const FALLBACK: [(&str, &str, &str); 2] = [
+        (
+            "AudioSpaceById",
+            "xVEzTKg_mLTHubK5ayL0HA",
+            "{\"spaces_2022_h2_clipping\":true,\"spaces_2022_h2_spaces_communities\":true,\"responsive_web_graphql_exclude_directive_enabled\":true,\"verified_phone_label_enabled\":false,\"creator_subscriptions_tweet_preview_api_enabled\":true,\"responsive_web_graphql_skip_user_profile_image_extensions_enabled\":false,\"tweetypie_unmention_optimization_enabled\":true,\"responsive_web_edit_tweet_api_enabled\":true,\"graphql_is_translatable_rweb_tweet_is_translatable_enabled\":true,\"view_counts_everywhere_api_enabled\":true,\"longform_notetweets_consumption_enabled\":true,\"responsive_web_twitter_article_tweet_consumption_enabled\":false,\"tweet_awards_web_tipping_enabled\":false,\"freedom_of_speech_not_reach_fetch_enabled\":true,\"standardized_nudges_misinfo\":true,\"tweet_with_visibility_results_prefer_gql_limited_actions_policy_enabled\":true,\"responsive_web_graphql_timeline_navigation_enabled\":true,\"longform_notetweets_rich_text_read_enabled\":true,\"longform_notetweets_inline_media_enabled\":true,\"responsive_web_media_download_video_enabled\":false,\"responsive_web_enhance_cards_enabled\":false}"
+        ),
+        ("ProfileSpotlightsQuery", "ZQEuHPrIYlvh1NAyIQHP_w", "{}")
+    ];
+    /// Minimum time between two bundle fetches, so a persistent 400 doesn't hammer the web client
+    const REFRESH_COOLDOWN: Duration = Duration::minutes(10);
+
+    pub fn new(web_url: &str) -> Self {
+        let headers = HeaderMap::from_iter([
+            (header::USER_AGENT, HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/132.0.0.0 Safari/537.36 Edg/132.0.0.0"))
+        ]);
+        Self { client: APIClient::new(Platform::TwitterSpace, web_url, headers, None), update_time: None, operations: HashMap::new() }
+    }
+
+    fn fallback(name: &str) -> Option<Operation> {
+        let (_, query_id, features) = Self::FALLBACK.iter().find(|(n, _, _)| *n == name)?;
+        Some(Operation { query_id: query_id.to_string(), features: serde_json::from_str(features).ok()? })
+    }
+
+    pub fn parse_bundle_url(html: &str) -> Option<&str> {
+        Some(Self::BUNDLE_REGEX.find(html)?.as_str())
+    }
+
+    pub fn parse_feature_values(html: &str) -> HashMap<String, bool> {
+        Self::FEATURE_VALUE_REGEX.captures_iter(html).map(|c| (c[1].to_owned(), &c[2] == "true")).collect()
+    }
+
+    pub fn parse_operations(bundle: &str, values: &HashMap<String, bool>) -> HashMap<String, Operation> {
+        Self::OPERATION_REGEX.captures_iter(bundle).map(|c| {
+            let features = Self::FEATURE_NAME_REGEX.captures_iter(&c["features"])
+                .map(|f| (f[1].to_owned(), values.get(&f[1]).copied().unwrap_or(false)))
+                .collect();
+            (c["name"].to_owned(), Operation { query_id: c["query_id"].to_owned(), features })
+        }).collect()
+    }
+
+    /// Fetch the web client and its main bundle and replace the cached operations.
+    /// Does nothing if the last refresh happened less than [`Self::REFRESH_COOLDOWN`] ago.
+    pub async fn refresh(&mut self) -> Option<()> {
+        if self.update_time.is_some_and(|t| t + Self::REFRESH_COOLDOWN > Utc::now()) {
+            return Some(());
+        }
+        self.update_time = Some(Utc::now());
+        let html = self.client.get_text(&[]).await.log_ok("Failed to get Twitter web client")?;
+        let Some(bundle_url) = Self::parse_bundle_url(&html) else {
+            log::error!("Twitter web client bundle URL not found");
+            return None;
+        };
+        let bundle = self.client.get_text(&[bundle_url]).await.log_ok("Failed to get Twitter web client bundle")?;
+        let operations = Self::parse_operations(&bundle, &Self::parse_feature_values(&html));
+        if operations.is_empty() {
+            log::error!("No GraphQL operations found in {bundle_url}");
+            return None;
+        }
+        log::info!("Discovered {} GraphQL operations from {bundle_url}", operations.len());
+        self.operations = operations;
+        Some(())
+    }
+
+    /// Get the cached operation, discovering it on first use and falling back to the built-in values.
+    pub async fn get(&mut self, name: &str) -> Option<Operation> {
+        if self.update_time.is_none() {
+            self.refresh().await;
+        }
+        self.operations.get(name).cloned().or_else(|| Self::fallback(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTML: &str = include_str!("../../../tests/fixtures/twitter/home.html");
+    const BUNDLE: &str = include_str!("../../../tests/fixtures/twitter/main.js");
+
+    #[test]
+    fn parse_bundle_url() {
+        assert_eq!(
+            Operations::parse_bundle_url(HTML),
+            Some("https://abs.twimg.com/responsive-web/client-web/main.a1b2c3d4.js")
+        );
+    }
+
+    #[test]
+    fn parse_operations() {
+        let operations = Operations::parse_operations(BUNDLE, &Operations::parse_feature_values(HTML));
+        let audio_space = &operations["AudioSpaceById"];
+        assert_eq!(audio_space.query_id, "rC8DT6zdR9o2ChNXr2PMKw");
+        assert_eq!(audio_space.features.get("spaces_2022_h2_clipping"), Some(&true));
+        assert_eq!(audio_space.features.get("verified_phone_label_enabled"), Some(&false));
+        // not present in the initial state, disabled by default
+        assert_eq!(audio_space.features.get("spaces_2025_h1_new_feature"), Some(&false));
+        let spotlights = &operations["ProfileSpotlightsQuery"];
+        assert_eq!(spotlights.query_id, "9zwVLJ48lmVUk8u_Gh9DmA");
+        assert_eq!(spotlights.features_json(), None);
+    }
+
+    #[test]
+    fn fallback() {
+        let operation = Operations::fallback("AudioSpaceById").unwrap();
+        assert_eq!(operation.query_id, "xVEzTKg_mLTHubK5ayL0HA");
+        assert_eq!(operation.features.len(), 21);
+        assert!(Operations::fallback("UserByScreenName").is_none());
+    }
+}
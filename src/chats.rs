@@ -0,0 +1,69 @@
+use std::collections::BTreeSet;
+
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisResult};
+use teloxide::types::ChatId;
+
+use crate::{handlers::command::Action, schema, subscription::Subscription, watcher::sinks::targets};
+
+/// Set of the chats banned by the owner
+const BANNED: &str = "banned";
+
+/// Chats subscribed to at least one live or appearance
+pub async fn subscribers(db: &mut MultiplexedConnection) -> RedisResult<BTreeSet<String>> {
+    let (subs, appearances): (Vec<Subscription>, Vec<Subscription>) = redis::pipe()
+        .hkeys(schema::key("subs")).hkeys(schema::key("appearances")).query_async(db).await?;
+    let mut chats = BTreeSet::new();
+    for sub in subs {
+        chats.extend(db.hkeys::<_, Vec<String>>(sub.key()).await?);
+    }
+    for sub in appearances {
+        chats.extend(db.smembers::<_, Vec<String>>(sub.appearances_key()).await?);
+    }
+    Ok(chats)
+}
+
+/// Chats subscribed to the lives of the user, and chats subscribed to its appearances
+pub async fn subscribers_of(
+    db: &mut MultiplexedConnection, sub: &Subscription
+) -> RedisResult<(Vec<String>, Vec<String>)> {
+    redis::pipe().hkeys(sub.key()).smembers(sub.appearances_key()).query_async(db).await
+}
+
+pub async fn is_banned(db: &mut MultiplexedConnection, chat_id: ChatId) -> RedisResult<bool> {
+    db.sismember(schema::key(BANNED), chat_id.to_string()).await
+}
+
+pub async fn banned(db: &mut MultiplexedConnection) -> RedisResult<usize> {
+    db.scard(schema::key(BANNED)).await
+}
+
+/// Ban the chat and remove its subscriptions, returning how many were removed
+pub async fn ban(db: &mut MultiplexedConnection, chat_id: ChatId) -> RedisResult<usize> {
+    let subs_key = Action::Subscribe.chat_key(chat_id);
+    let appearances_key = Action::Appear.chat_key(chat_id);
+    let (subs, appearances): (Vec<Subscription>, Vec<Subscription>) = redis::pipe()
+        .smembers(&subs_key).smembers(&appearances_key).query_async(db).await?;
+    let mut pipe = redis::pipe();
+    pipe.atomic().sadd(schema::key(BANNED), chat_id.to_string()).del(&subs_key).del(&appearances_key);
+    for sub in &subs {
+        pipe.hdel(sub.key(), chat_id.to_string());
+        // subscriptions bound to targets are still polled for them
+        if db.hlen::<_, usize>(sub.key()).await? == 1 && targets::count(db, sub).await? == 0 {
+            pipe.hdel(schema::key("subs"), sub);
+        }
+    }
+    for sub in &appearances {
+        let key = sub.appearances_key();
+        pipe.srem(&key, chat_id.to_string());
+        if db.scard::<_, usize>(&key).await? == 1 {
+            pipe.hdel(schema::key("appearances"), sub);
+        }
+    }
+    pipe.exec_async(db).await?;
+    Ok(subs.len() + appearances.len())
+}
+
+/// Lift the ban of the chat, whose subscriptions are not restored, returning whether it was banned
+pub async fn unban(db: &mut MultiplexedConnection, chat_id: ChatId) -> RedisResult<bool> {
+    db.srem(schema::key(BANNED), chat_id.to_string()).await
+}
@@ -0,0 +1,94 @@
+use std::{collections::HashMap, env, fs, io, sync::RwLock};
+
+use lazy_static::lazy_static;
+
+/// Variables read once at startup, which `/reload` can't apply
+pub const RESTART_REQUIRED: [&str; 12] = [
+    "TELOXIDE_TOKEN",
+    "REDIS_URL",
+    "REDIS_KEY_PREFIX",
+    "LISTEN_ADDR",
+    "TELEGRAM_WEBHOOK_URL",
+    "TELEGRAM_WEBHOOK_PATH",
+    "TELEGRAM_WEBHOOK_SECRET",
+    "TELEGRAM_WEBHOOK_CERTIFICATE",
+    "TWITTER_API_URL",
+    "TWITTER_WEB_URL",
+    "BILIBILI_API_URL",
+    "BILIBILI_NAV_URL"
+];
+
+lazy_static! {
+    /// Variables set by the env file, which take precedence over the environment. They are kept apart, since changing
+    /// the environment of the process while other threads read it is undefined behavior.
+    static ref VARIABLES: RwLock<HashMap<String, String>> = RwLock::default();
+}
+
+/// Value of the variable, from the env file if it sets it, or from the environment
+pub fn var(key: &str) -> Result<String, env::VarError> {
+    match VARIABLES.read().expect("failed to lock config").get(key) {
+        Some(value) => Ok(value.clone()),
+        None => env::var(key)
+    }
+}
+
+/// File of `KEY=value` lines given by `ENV_FILE`, loaded at startup and by `/reload`
+pub fn env_file() -> Option<String> {
+    env::var("ENV_FILE").ok()
+}
+
+/// Variables of an env file, without the blank lines, the comments and the quotes around the values
+fn parse(content: &str) -> Vec<(String, String)> {
+    content.lines().filter_map(|line| {
+        let line = line.trim();
+        if line.starts_with('#') {
+            return None;
+        }
+        let (key, value) = line.strip_prefix("export ").unwrap_or(line).split_once('=')?;
+        let value = value.trim();
+        let value = [('"', '"'), ('\'', '\'')].iter()
+            .find_map(|(start, end)| value.strip_prefix(*start)?.strip_suffix(*end))
+            .unwrap_or(value);
+        Some((key.trim().to_owned(), value.to_owned()))
+    }).collect()
+}
+
+/// Set the variables of the env file, returning the names of those which changed.
+/// Variables removed from the file keep their value until the bot restarts.
+pub fn load() -> io::Result<Vec<String>> {
+    let Some(path) = env_file() else {
+        return Ok(vec![]);
+    };
+    let mut changed = vec![];
+    for (key, value) in parse(&fs::read_to_string(path)?) {
+        if var(&key).is_ok_and(|current| current == value) {
+            continue;
+        }
+        VARIABLES.write().expect("failed to lock config").insert(key.clone(), value);
+        changed.push(key);
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn var() {
+        VARIABLES.write().unwrap().insert("TELESCOPE_TEST_VARIABLE".to_owned(), "file".to_owned());
+        assert_eq!(super::var("TELESCOPE_TEST_VARIABLE").unwrap(), "file");
+        assert!(super::var("TELESCOPE_UNSET_VARIABLE").is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let content = "# Telescope\nBOT_OWNER=42\n\nexport TWITTER_AUTH_TOKEN = \"a,b\"\n\
+            WEBHOOK_SECRET='s=1'\ninvalid\n";
+        assert_eq!(super::parse(content), [
+            ("BOT_OWNER".to_owned(), "42".to_owned()),
+            ("TWITTER_AUTH_TOKEN".to_owned(), "a,b".to_owned()),
+            ("WEBHOOK_SECRET".to_owned(), "s=1".to_owned())
+        ]);
+    }
+}
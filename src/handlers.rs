@@ -1,2 +1,43 @@
+use callback::callback_handler;
+use command::{command_handler, Command};
+use owner::{is_owner, owner_command_handler, OwnerCommand};
+use redis::aio::MultiplexedConnection;
+use teloxide::{
+    dispatching::{UpdateFilterExt, UpdateHandler},
+    filter_command,
+    prelude::Requester,
+    types::{Message, Update},
+    RequestError
+};
+
+use crate::{chats, i18n, log_utils::LogResult, Bot};
+
 pub mod command;
 pub mod callback;
+pub mod owner;
+
+/// Whether the update comes from a chat banned by the owner
+async fn banned(update: Update, mut db: MultiplexedConnection) -> bool {
+    let Some(chat) = update.chat() else {
+        return false;
+    };
+    chats::is_banned(&mut db, chat.id).await.log_ok("Failed to check ban").unwrap_or(false)
+}
+
+/// Handler of the updates, dispatching the commands and the callback queries, and dropping the updates of banned chats
+pub fn schema() -> UpdateHandler<RequestError> {
+    dptree::entry().branch(
+        dptree::filter_async(banned).endpoint(async || Ok(()))
+    ).branch(
+        Update::filter_message().branch(
+            filter_command::<OwnerCommand, _>().filter(is_owner).endpoint(owner_command_handler)
+        ).branch(
+            filter_command::<Command, _>().endpoint(command_handler)
+        ).endpoint(async |bot: Bot, msg: Message, mut db: MultiplexedConnection| {
+            let lang = i18n::resolve(&mut db, msg.chat.id, msg.from.as_ref()).await;
+            bot.send_message(msg.chat.id, lang.markdown("unknown_command", &[])).await.and(Ok(()))
+        })
+    ).branch(
+        Update::filter_callback_query().endpoint(callback_handler)
+    )
+}
@@ -1,30 +1,41 @@
 use std::{future::Future, io::Error, num::NonZero};
 
 use redis::{aio::MultiplexedConnection, AsyncTypedCommands, RedisResult};
+use strum::IntoEnumIterator;
 use teloxide::{
     payloads::AnswerCallbackQuerySetters,
     prelude::Requester,
     types::{CallbackQuery, Message},
-    utils::markdown::escape,
     RequestError
 };
 
-use super::command::Action;
-use crate::{subscription::fmt_subscriptions, Bot};
-
-const EXPIRED_MESSAGE: &str = "Message expired, please use the command again";
+use super::command::{pending_key, Action, SAVE_TEMPLATE};
+use crate::{
+    access,
+    i18n::{self, Language},
+    platform::Platform,
+    schema,
+    subscription::{fmt_subscriptions, Subscription},
+    templates,
+    watcher::sinks::targets,
+    Bot
+};
 
-async fn error_callback_query(bot: &Bot, query: &CallbackQuery, msg: &Message, text: &str) -> Result<(), RequestError> {
-    bot.answer_callback_query(query.id.clone()).text(text).await?;
-    bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+async fn error_callback_query(
+    bot: &Bot, query: &CallbackQuery, msg: &Message, lang: Language, key: &str
+) -> Result<(), RequestError> {
+    bot.answer_callback_query(query.id.clone()).text(lang.text(key, &[])).await?;
+    bot.edit_message_text(msg.chat.id, msg.id, lang.markdown(key, &[])).await?;
     Ok(())
 }
 
-async fn try_db<RV>(r: impl Future<Output = RedisResult<RV>>, bot: &Bot, query: &CallbackQuery) -> Result<RV, RequestError> {
+async fn try_db<RV>(
+    r: impl Future<Output = RedisResult<RV>>, bot: &Bot, query: &CallbackQuery, lang: Language
+) -> Result<RV, RequestError> {
     match r.await {
         Ok(o) => Ok(o),
         Err(e) => {
-            bot.answer_callback_query(query.id.clone()).text(format!("Database error: {}", escape(&e.to_string()))).await?;
+            bot.answer_callback_query(query.id.clone()).text(lang.text("database_error", &[("error", &e)])).await?;
             Err(RequestError::Io(Error::new(std::io::ErrorKind::Other, "Database error").into()))
         }
     }
@@ -32,47 +43,95 @@ async fn try_db<RV>(r: impl Future<Output = RedisResult<RV>>, bot: &Bot, query:
 
 pub async fn callback_handler(bot: Bot, query: CallbackQuery, mut db: MultiplexedConnection) -> Result<(), RequestError> {
     let Some(msg) = query.regular_message() else {
-        return bot.answer_callback_query(query.id).text(EXPIRED_MESSAGE).await.and(Ok(()));
+        let lang = Language::from_code(query.from.language_code.as_deref());
+        return bot.answer_callback_query(query.id).text(lang.text("callback.expired", &[])).await.and(Ok(()));
     };
+    let lang = i18n::resolve(&mut db, msg.chat.id, Some(&query.from)).await;
     let Some(data) = &query.data else {
-        return bot.answer_callback_query(query.id).text("Invalid callback data").await.and(Ok(()));
+        return bot.answer_callback_query(query.id).text(lang.text("callback.invalid", &[])).await.and(Ok(()));
     };
-    let key = format!("{}:{}", msg.chat.id, msg.id);
+    let key = pending_key(msg.chat.id, msg.id);
     if data == "cancel" {  // handle cancel callback first
-        try_db(db.del(&key), &bot, &query).await?;
-        return error_callback_query(&bot, &query, msg, "Cancelled").await;
+        try_db(db.del(&key), &bot, &query, lang).await?;
+        return error_callback_query(&bot, &query, msg, lang, "callback.cancelled").await;
     }
-    let len = NonZero::new(try_db(db.llen(&key), &bot, &query).await?);
+    let len = NonZero::new(try_db(db.llen(&key), &bot, &query, lang).await?);
     if len.is_none() {
-        return error_callback_query(&bot, &query, msg, EXPIRED_MESSAGE).await;
+        return error_callback_query(&bot, &query, msg, lang, "callback.expired").await;
+    }
+    if data == SAVE_TEMPLATE {
+        let values: Vec<String> = try_db(db.lpop(&key, len), &bot, &query, lang).await?;
+        let [platform, event, source] = &values[..] else {
+            return error_callback_query(&bot, &query, msg, lang, "callback.expired").await;
+        };
+        let (Some(platform), Ok(event)) = (Platform::iter().find(|p| p.short_name() == platform), event.parse()) else {
+            return error_callback_query(&bot, &query, msg, lang, "callback.expired").await;
+        };
+        try_db(templates::save(&mut db, msg.chat.id, &platform, &event, source), &bot, &query, lang).await?;
+        bot.answer_callback_query(query.id.clone()).text(lang.text("callback.template_saved", &[])).await?;
+        bot.edit_message_reply_markup(msg.chat.id, msg.id).await?;
+        return Ok(());
+    }
+    let subs: Vec<Subscription> = try_db(db.lpop(&key, len), &bot, &query, lang).await?;
+    if matches!(data.parse(), Ok(Action::Subscribe | Action::Appear)) {
+        let remaining = access::remaining_subscriptions(&mut db, msg.chat.id);
+        let remaining = try_db(remaining, &bot, &query, lang).await?;
+        if remaining.is_some_and(|remaining| subs.len() > remaining) {
+            let max = access::max_subscriptions().unwrap_or_default();
+            bot.answer_callback_query(query.id.clone()).text(lang.text("callback.quota", &[("max", &max)])).await?;
+            bot.edit_message_text(msg.chat.id, msg.id, lang.markdown("callback.quota", &[("max", &max)])).await?;
+            return Ok(());
+        }
     }
-    let subs = try_db(db.lpop(&key, len), &bot, &query).await?;
     let mut pipe = redis::pipe();
     let mut pipe = pipe.atomic();
     let (text, pipe) = match data.parse() {
         Ok(Action::Subscribe) => {
             for sub in &subs {
                 pipe = pipe
-                    .hset("subs", sub, "")
-                    .hset(sub, query.from.id.to_string(), 0)
-                    .sadd(query.from.id.to_string(), sub);
+                    .hset(schema::key("subs"), sub, "")
+                    .hset(sub.key(), msg.chat.id.to_string(), 0)
+                    .sadd(Action::Subscribe.chat_key(msg.chat.id), sub);
             }
-            (format!("You have successfully subscribed to:\n{}", fmt_subscriptions(&subs)), pipe)
+            (lang.markdown("callback.subscribe", &[("subs", &fmt_subscriptions(&subs))]), pipe)
         }
         Ok(Action::Unsubscribe) => {
             for sub in &subs {
                 pipe = pipe
-                    .srem(query.from.id.to_string(), sub)
-                    .hdel(sub, query.from.id.to_string());
-                if try_db(db.hlen(sub), &bot, &query).await? == 1 {
-                    pipe = pipe.hdel("subs", sub)
+                    .srem(Action::Subscribe.chat_key(msg.chat.id), sub)
+                    .hdel(sub.key(), msg.chat.id.to_string());
+                // subscriptions bound to targets are still polled for them
+                if try_db(db.hlen(sub.key()), &bot, &query, lang).await? == 1
+                    && try_db(targets::count(&mut db, sub), &bot, &query, lang).await? == 0 {
+                    pipe = pipe.hdel(schema::key("subs"), sub)
+                }
+            }
+            (lang.markdown("callback.unsubscribe", &[("subs", &fmt_subscriptions(&subs))]), pipe)
+        }
+        Ok(Action::Appear) => {
+            for sub in &subs {
+                pipe = pipe
+                    .hset(schema::key("appearances"), sub, "")
+                    .sadd(sub.appearances_key(), msg.chat.id.to_string())
+                    .sadd(Action::Appear.chat_key(msg.chat.id), sub);
+            }
+            (lang.markdown("callback.appear", &[("subs", &fmt_subscriptions(&subs))]), pipe)
+        }
+        Ok(Action::Unappear) => {
+            for sub in &subs {
+                let key = sub.appearances_key();
+                pipe = pipe
+                    .srem(Action::Appear.chat_key(msg.chat.id), sub)
+                    .srem(&key, msg.chat.id.to_string());
+                if try_db(db.scard(&key), &bot, &query, lang).await? == 1 {
+                    pipe = pipe.hdel(schema::key("appearances"), sub)
                 }
             }
-            (format!("You have successfully unsubscribed to:\n{}", fmt_subscriptions(&subs)), pipe)
+            (lang.markdown("callback.unappear", &[("subs", &fmt_subscriptions(&subs))]), pipe)
         }
-        Err(_) => ("Why are we still here? Just to suffer?".to_owned(), pipe)
+        Err(_) => (lang.markdown("callback.unknown", &[]), pipe)
     };
-    try_db(pipe.del(key).exec_async(&mut db), &bot, &query).await?;
+    try_db(pipe.del(key).exec_async(&mut db), &bot, &query, lang).await?;
     bot.edit_message_text(msg.chat.id, msg.id, text).await?;
     Ok(())
 }
@@ -1,18 +1,30 @@
-use redis::{aio::MultiplexedConnection, AsyncCommands};
+use std::fmt::Display;
+
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisError, RedisResult};
 use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumString};
 use teloxide::{
     payloads::SendMessageSetters,
     prelude::Requester,
     sugar::request::RequestLinkPreviewExt,
-    types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message},
-    utils::{command::BotCommands, markdown::escape},
+    types::{BotCommand, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId},
+    utils::{command::BotCommands, markdown::{code_block, code_inline, escape}},
     RequestError
 };
+use url::Url;
 
+use super::owner;
 use crate::{
+    access::{self, RateLimit},
+    i18n::{self, Language},
+    log_utils::LogResult,
     platform::Platform,
-    subscription::{fmt_subscriptions, Subscription},
+    schema,
+    server::{self, feeds},
+    settings::Setting,
+    subscription::{fmt_subscriptions, Subscription, UrlError},
+    templates::{self, Event, Placeholder, Template},
+    watcher::sinks::webhook::{self, SIGNATURE_HEADER},
     Bot
 };
 
@@ -20,7 +32,7 @@ use crate::{
 #[command(rename_rule = "lowercase")]
 pub enum Command {
     /// Start the bot
-    Start,
+    Start(String),
     /// Print the help message
     Help,
     /// Subscribe to the live stream from the specified URL. You can specify multiple URLs by separating them by spaces.
@@ -29,45 +41,97 @@ pub enum Command {
     /// Remove subscription to the live stream from the specified URL. You can specify multiple URLs by separating them by spaces.
     /// e.g. /del https://twitter.com/username
     Del(String),
+    /// Subscribe to the appearances of the Twitter user from the specified URL as co-host or speaker in other users' Spaces.
+    /// e.g. /appear https://twitter.com/username
+    Appear(String),
+    /// Remove subscription to the appearances of the Twitter user from the specified URL.
+    /// e.g. /unappear https://twitter.com/username
+    Unappear(String),
     /// List existing subscriptions
     List,
     /// List all supported platforms
-    Platform
+    Platform,
+    /// Change a setting of this chat, or show the current settings if no argument is given.
+    /// e.g. /set edit_in_place on
+    Set(String),
+    /// Customize the notification message of a platform and event, or show the help without arguments.
+    /// e.g. /template twitter start *{creator}* is live: [{title}]({url})
+    Template(String),
+    /// Change the language of the bot in this chat, or show the available languages if no argument is given.
+    /// e.g. /language en
+    Language(String),
+    /// Send the live events of the subscriptions of this chat to a webhook, signed with the secret, or show the current webhook if no argument is given.
+    /// e.g. /webhook https://example.com/telescope secret
+    Webhook(String),
+    /// Show the URLs of the Atom and JSON feeds and of the calendar of the lives of your subscriptions, or replace them with /feed reset.
+    Feed(String)
+}
+
+/// Bot commands described in the language, the doc comments above being the English descriptions
+pub fn localized_commands(language: Language) -> Vec<BotCommand> {
+    Command::bot_commands().into_iter().map(|mut command| {
+        if let Some(description) = language.find(&format!("command.{}", command.command.trim_start_matches('/'))) {
+            command.description = description.to_owned();
+        }
+        command
+    }).collect()
 }
 
 #[derive(Display, EnumString)]
 #[strum(serialize_all = "lowercase")]
 pub enum Action {
     Subscribe,
-    Unsubscribe
+    Unsubscribe,
+    Appear,
+    Unappear
+}
+
+impl Action {
+    /// Key of the set holding the subscriptions of the chat affected by this action
+    pub fn chat_key(&self, chat_id: ChatId) -> String {
+        match self {
+            Action::Subscribe | Action::Unsubscribe => schema::key(format!("chat:{chat_id}")),
+            Action::Appear | Action::Unappear => schema::key(format!("appearances:{chat_id}"))
+        }
+    }
+}
+
+/// Key of the list holding the values confirmed by the buttons of the message, which expires after a day
+pub fn pending_key(chat_id: ChatId, message_id: MessageId) -> String {
+    schema::key(format!("pending:{chat_id}:{message_id}"))
+}
+
+/// Callback data of the confirmation of a template preview
+pub const SAVE_TEMPLATE: &str = "template";
+
+pub fn database_error(lang: Language, error: &RedisError) -> String {
+    lang.markdown("database_error", &[("error", &escape(&error.to_string()))])
 }
 
-fn make_reply_markup(action: Action) -> InlineKeyboardMarkup {
+fn make_reply_markup(lang: Language, data: impl Display) -> InlineKeyboardMarkup {
     let keyboard: [[InlineKeyboardButton; 2]; 1] = [
         [
-            InlineKeyboardButton::callback("✅ Confirm", action.to_string()),
-            InlineKeyboardButton::callback("❌ Cancel", "cancel".to_owned())
+            InlineKeyboardButton::callback(lang.get("button.confirm"), data.to_string()),
+            InlineKeyboardButton::callback(lang.get("button.cancel"), "cancel".to_owned())
         ]
     ];
     InlineKeyboardMarkup::new(keyboard)
 }
 
 async fn send_reply(
-    bot: &Bot, chat_id: ChatId, db: &mut MultiplexedConnection, subs: &Vec<Subscription>, action: Action
+    bot: &Bot, chat_id: ChatId, db: &mut MultiplexedConnection, lang: Language, subs: &Vec<Subscription>, action: Action
 ) -> Result<(), RequestError> {
-    let reply = bot.send_message(
-        chat_id,
-        format!("Please confirm that you want to {action} to the following users:\n{}", fmt_subscriptions(&subs))
-    ).reply_markup(make_reply_markup(action)).await?;
-    let key = format!("{}:{}", reply.chat.id, reply.id);
+    let reply = bot.send_message(chat_id, lang.markdown(&format!("confirm.{action}"), &[("subs", &fmt_subscriptions(subs))]))
+        .reply_markup(make_reply_markup(lang, action)).await?;
+    let key = pending_key(reply.chat.id, reply.id);
     if let Err(e) = redis::pipe().atomic().rpush(&key, subs).expire(&key, 86400).exec_async(db).await {
-        bot.edit_message_text(reply.chat.id, reply.id, format!("Database error: {}", escape(&e.to_string()))).await?;
+        bot.edit_message_text(reply.chat.id, reply.id, database_error(lang, &e)).await?;
     }
     Ok(())
 }
 
 async fn process_urls(
-    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, urls: String, action: Action
+    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, lang: Language, urls: String, action: Action
 ) -> Result<(), RequestError> {
     let mut subs = vec![];
     let mut errors = vec![];
@@ -76,59 +140,347 @@ async fn process_urls(
         (!url.is_empty()).then_some(url)
     }) {
         match Subscription::from_url(url.to_owned()).await {
+            Ok(sub) if matches!(action, Action::Appear | Action::Unappear) && !matches!(sub.platform, Platform::TwitterSpace) => {
+                errors.push(lang.markdown(
+                    "urls.appearance_unsupported",
+                    &[("url", &escape(url)), ("platform", &escape(&Platform::TwitterSpace.to_string()))]
+                ))
+            }
             Ok(sub) => {
-                match (db.sismember(msg.chat.id.0, &sub).await, &action) {
-                    (Ok(true), Action::Subscribe) => errors.push(format!("{}: You have already subscribed to {sub}", escape(url))),
-                    (Ok(true), Action::Unsubscribe) => subs.push(sub),
-                    (Ok(false), Action::Subscribe) => subs.push(sub),
-                    (Ok(false), Action::Unsubscribe) => errors.push(format!("{}: You are not subscribed to {sub}", escape(url))),
+                match (db.sismember(action.chat_key(msg.chat.id), &sub).await, &action) {
+                    (Ok(true), Action::Subscribe | Action::Appear) => {
+                        errors.push(lang.markdown("urls.already_subscribed", &[("url", &escape(url)), ("sub", &sub)]))
+                    }
+                    (Ok(true), Action::Unsubscribe | Action::Unappear) => subs.push(sub),
+                    (Ok(false), Action::Subscribe | Action::Appear) => subs.push(sub),
+                    (Ok(false), Action::Unsubscribe | Action::Unappear) => {
+                        errors.push(lang.markdown("urls.not_subscribed", &[("url", &escape(url)), ("sub", &sub)]))
+                    }
                     (Err(e), _) => {
-                        errors.push(format!("Database error: {}", escape(&e.to_string())));
+                        errors.push(database_error(lang, &e));
                         break;
                     }
                 }
             },
-            Err(e) => errors.push(format!("{}: {}", escape(url), escape(&e.to_string())))
+            Err(e) => {
+                if let UrlError::Lookup(_, api_error) = &e {
+                    if api_error.needs_attention() {
+                        owner::notify(bot, &format!("Failed to subscribe to {url}: {e}")).await;
+                    }
+                }
+                errors.push(lang.markdown("urls.invalid", &[("url", &escape(url)), ("error", &e.message(lang))]))
+            }
+        }
+    }
+    if matches!(action, Action::Subscribe | Action::Appear) && !subs.is_empty() {
+        match access::remaining_subscriptions(db, msg.chat.id).await {
+            Ok(Some(remaining)) if subs.len() > remaining => {
+                let max = access::max_subscriptions().unwrap_or_default();
+                errors.push(lang.markdown("urls.quota", &[("max", &max), ("skipped", &(subs.len() - remaining))]));
+                subs.truncate(remaining);
+            }
+            Ok(_) => (),
+            Err(e) => {
+                errors.push(database_error(lang, &e));
+                subs.clear();
+            }
         }
     }
     if errors.len() > 0 {
         bot.send_message(msg.chat.id, errors.join("\n")).disable_link_preview(true).await?;
     }
     if subs.len() > 0 {
-        send_reply(bot, msg.chat.id, db, &subs, action).await?;
+        send_reply(bot, msg.chat.id, db, lang, &subs, action).await?;
     }
     if errors.len() == 0 && subs.len() == 0 {
-        bot.send_message(msg.chat.id, "Nothing to do").await?;
+        bot.send_message(msg.chat.id, lang.markdown("nothing_to_do", &[])).await?;
+    }
+    Ok(())
+}
+
+async fn process_setting(
+    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, lang: Language, args: String
+) -> Result<(), RequestError> {
+    let args: Vec<_> = args.split_whitespace().collect();
+    let text = match args[..] {
+        [] => {
+            let mut lines = vec![];
+            for setting in Setting::iter() {
+                let value = match setting.get(db, msg.chat.id).await {
+                    Ok(true) => "on",
+                    Ok(false) => "off",
+                    Err(e) => return bot.send_message(msg.chat.id, database_error(lang, &e)).await.and(Ok(()))
+                };
+                lines.push(format!("{setting}: {value}\n{}", lang.get(&format!("setting.{setting}"))));
+            }
+            lang.markdown("settings.list", &[("settings", &escape(&lines.join("\n\n")))])
+        }
+        [name, value @ ("on" | "off")] => match name.parse::<Setting>() {
+            Ok(setting) => match setting.set(db, msg.chat.id, value == "on").await {
+                Ok(()) => lang.markdown("settings.changed", &[("setting", &escape(&setting.to_string())), ("value", &value)]),
+                Err(e) => database_error(lang, &e)
+            },
+            Err(_) => lang.markdown("settings.unknown", &[("name", &escape(name))])
+        },
+        _ => lang.markdown("settings.usage", &[])
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+fn template_help(lang: Language) -> String {
+    let platforms = Platform::iter().map(|p| p.short_name()).collect::<Vec<_>>().join("|");
+    let events = Event::iter().map(|e| e.to_string()).collect::<Vec<_>>().join("|");
+    let placeholders = Placeholder::iter()
+        .map(|p| format!("{{{p}}}: {}", lang.get(&format!("placeholder.{p}")))).collect::<Vec<_>>().join("\n");
+    lang.markdown(
+        "template.help",
+        &[("platforms", &escape(&platforms)), ("events", &escape(&events)), ("placeholders", &escape(&placeholders))]
+    )
+}
+
+async fn process_template(
+    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, lang: Language, args: String
+) -> Result<(), RequestError> {
+    let mut args = args.trim().splitn(3, char::is_whitespace);
+    let (Some(platform), Some(event)) = (args.next().filter(|s| !s.is_empty()), args.next()) else {
+        return bot.send_message(msg.chat.id, template_help(lang)).await.and(Ok(()));
+    };
+    let Some(platform) = Platform::iter().find(|p| p.short_name() == platform) else {
+        let text = lang.markdown("template.unsupported_platform", &[("platform", &escape(platform))]);
+        return bot.send_message(msg.chat.id, text).await.and(Ok(()));
+    };
+    let Ok(event) = event.parse::<Event>() else {
+        return bot.send_message(msg.chat.id, lang.markdown("template.unknown_event", &[("event", &escape(event))])).await.and(Ok(()));
+    };
+    let text = match args.next().map(str::trim) {
+        None => match templates::get(db, msg.chat.id, &platform, &event).await {
+            Ok(source) => lang.markdown(
+                "template.current",
+                &[("template", &code_block(source.as_deref().unwrap_or(Template::default_source(lang, &platform, &event))))]
+            ),
+            Err(e) => database_error(lang, &e)
+        },
+        Some("reset") => match templates::reset(db, msg.chat.id, &platform, &event).await {
+            Ok(()) => lang.markdown("template.restored", &[]),
+            Err(e) => database_error(lang, &e)
+        },
+        Some(source) => {
+            let template = match source.parse::<Template>() {
+                Ok(template) => template,
+                Err(e) => {
+                    let text = lang.markdown("template.invalid", &[("error", &escape(&e.to_string()))]);
+                    return bot.send_message(msg.chat.id, text).await.and(Ok(()));
+                }
+            };
+            bot.send_message(msg.chat.id, lang.markdown("template.preview", &[])).await?;
+            // Telegram rejecting the preview catches invalid MarkdownV2 before the template is used
+            let preview = bot.send_message(msg.chat.id, template.render(&templates::sample(&platform, &event)))
+                .reply_markup(make_reply_markup(lang, SAVE_TEMPLATE)).await;
+            let reply = match preview {
+                Ok(reply) => reply,
+                Err(RequestError::Api(e)) => {
+                    let text = lang.markdown("template.invalid", &[("error", &escape(&e.to_string()))]);
+                    return bot.send_message(msg.chat.id, text).await.and(Ok(()));
+                }
+                Err(e) => return Err(e)
+            };
+            let key = pending_key(reply.chat.id, reply.id);
+            let values = [platform.short_name(), &event.to_string(), source];
+            if let Err(e) = redis::pipe().atomic().rpush(&key, &values).expire(&key, 86400).exec_async(db).await {
+                bot.edit_message_text(reply.chat.id, reply.id, database_error(lang, &e)).await?;
+            }
+            return Ok(());
+        }
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+async fn process_language(
+    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, lang: Language, code: String
+) -> Result<(), RequestError> {
+    let code = code.trim();
+    let text = if code.is_empty() {
+        let languages = Language::iter().map(|l| format!("{l}: {}", l.name())).collect::<Vec<_>>().join("\n");
+        lang.markdown("language.current", &[("language", &escape(lang.name())), ("languages", &escape(&languages))])
+    } else {
+        match code.to_lowercase().parse::<Language>() {
+            Ok(language) => match i18n::set(db, msg.chat.id, language).await {
+                Ok(()) => language.markdown("language.changed", &[("language", &escape(language.name()))]),
+                Err(e) => database_error(lang, &e)
+            },
+            Err(_) => lang.markdown("language.unsupported", &[("code", &escape(code))])
+        }
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Whether the sender of the message administers the chat, which everyone does in private chats
+async fn is_admin(bot: &Bot, msg: &Message) -> Result<bool, RequestError> {
+    if msg.chat.is_private() {
+        return Ok(true);
+    }
+    // anonymous administrators send messages on behalf of the chat
+    if msg.sender_chat.as_ref().is_some_and(|chat| chat.id == msg.chat.id) {
+        return Ok(true);
     }
+    let Some(user) = &msg.from else {
+        return Ok(false);
+    };
+    Ok(bot.get_chat_member(msg.chat.id, user.id).await?.is_privileged())
+}
+
+async fn process_webhook(
+    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, lang: Language, args: String
+) -> Result<(), RequestError> {
+    if !is_admin(bot, msg).await? {
+        return bot.send_message(msg.chat.id, lang.markdown("webhook.admin_only", &[])).await.and(Ok(()));
+    }
+    let args: Vec<_> = args.split_whitespace().collect();
+    let text = match args[..] {
+        [] => match webhook::get(db, msg.chat.id).await {
+            Ok(Some(webhook)) => lang.markdown("webhook.current", &[("url", &escape(webhook.url().as_str()))]),
+            Ok(None) => lang.markdown("webhook.none", &[]),
+            Err(e) => database_error(lang, &e)
+        },
+        ["reset"] => match webhook::remove(db, msg.chat.id).await {
+            Ok(()) => lang.markdown("webhook.removed", &[]),
+            Err(e) => database_error(lang, &e)
+        },
+        [url, secret] => match url.parse::<Url>() {
+            Ok(url) if url.scheme() == "https" && webhook::is_public(&url).await => {
+                // keep the secret out of the chat history
+                bot.delete_message(msg.chat.id, msg.id).await.log_ok("Failed to delete webhook command");
+                match webhook::set(db, msg.chat.id, &url, secret).await {
+                    Ok(()) => lang.markdown(
+                        "webhook.saved",
+                        &[("url", &escape(url.as_str())), ("header", &code_inline(SIGNATURE_HEADER))]
+                    ),
+                    Err(e) => database_error(lang, &e)
+                }
+            }
+            _ => lang.markdown("webhook.invalid_url", &[("url", &escape(url))])
+        },
+        _ => lang.markdown("webhook.usage", &[])
+    };
+    bot.send_message(msg.chat.id, text).disable_link_preview(true).await?;
+    Ok(())
+}
+
+async fn process_feed(
+    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, lang: Language, args: String
+) -> Result<(), RequestError> {
+    if server::public_url().is_none() {
+        return bot.send_message(msg.chat.id, lang.markdown("feed.disabled", &[])).await.and(Ok(()));
+    }
+    let token = match args.trim() {
+        "reset" => feeds::reset_token(db, msg.chat.id).await,
+        _ => feeds::token(db, msg.chat.id).await
+    };
+    let text = match token.map(|token| feeds::chat_feed_url(&token)) {
+        Ok(Some(url)) => lang.markdown("feed.urls", &[
+            ("atom", &code_inline(&format!("{url}.atom"))),
+            ("json", &code_inline(&format!("{url}.json"))),
+            ("ics", &code_inline(&format!("{url}.ics")))
+        ]),
+        Ok(None) => lang.markdown("feed.disabled", &[]),
+        Err(e) => database_error(lang, &e)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Check the rate limit of the user and whether the chat can use the bot, telling it why if it can't
+async fn check_access(
+    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, lang: Language, cmd: &Command
+) -> Result<bool, RequestError> {
+    if let Some(user) = &msg.from {
+        match access::count_command(db, user.id).await.log_ok("Failed to count command") {
+            Some(RateLimit::Exceeded) => {
+                return bot.send_message(msg.chat.id, lang.markdown("rate_limited", &[])).await.and(Ok(false));
+            }
+            Some(RateLimit::Ignored) => return Ok(false),
+            _ => ()
+        }
+    }
+    let text = match access::is_allowed(db, msg.chat.id).await {
+        Ok(true) => return Ok(true),
+        // the invite code is redeemed by the start command
+        Ok(false) if matches!(cmd, Command::Start(code) if !code.trim().is_empty()) => return Ok(true),
+        Ok(false) => lang.markdown("invite.required", &[]),
+        Err(e) => database_error(lang, &e)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(false)
+}
+
+async fn process_start(
+    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, lang: Language, code: String
+) -> Result<(), RequestError> {
+    let code = code.trim();
+    let text = if code.is_empty() || !access::invite_only() {
+        lang.markdown("start", &[])
+    } else {
+        match access::redeem(db, code, msg.chat.id).await {
+            Ok(true) => lang.markdown("invite.accepted", &[]),
+            Ok(false) => lang.markdown("invite.invalid", &[]),
+            Err(e) => database_error(lang, &e)
+        }
+    };
+    bot.send_message(msg.chat.id, text).await?;
     Ok(())
 }
 
 pub async fn command_handler(bot: Bot, msg: Message, cmd: Command, mut db: MultiplexedConnection) -> Result<(), RequestError> {
+    let lang = i18n::resolve(&mut db, msg.chat.id, msg.from.as_ref()).await;
+    if !check_access(&bot, &msg, &mut db, lang, &cmd).await? {
+        return Ok(());
+    }
     match cmd {
-        Command::Start => bot.send_message(
-            msg.chat.id, "Welcome to the Telescope bot\\. You can view a list of available commands using the /help command\\."
-        ).await?,
-        Command::Help => bot.send_message(msg.chat.id, escape(Command::descriptions().to_string().as_str())).await?,
-        Command::Sub(urls) => return process_urls(&bot, &msg, &mut db, urls, Action::Subscribe).await,
-        Command::Del(urls) => return process_urls(&bot, &msg, &mut db, urls, Action::Unsubscribe).await,
+        Command::Start(code) => return process_start(&bot, &msg, &mut db, lang, code).await,
+        Command::Help => {
+            let help = localized_commands(lang).iter()
+                .map(|c| format!("{} — {}", c.command, c.description)).collect::<Vec<_>>().join("\n");
+            bot.send_message(msg.chat.id, escape(&help)).await?
+        }
+        Command::Sub(urls) => return process_urls(&bot, &msg, &mut db, lang, urls, Action::Subscribe).await,
+        Command::Del(urls) => return process_urls(&bot, &msg, &mut db, lang, urls, Action::Unsubscribe).await,
+        Command::Appear(urls) => return process_urls(&bot, &msg, &mut db, lang, urls, Action::Appear).await,
+        Command::Unappear(urls) => return process_urls(&bot, &msg, &mut db, lang, urls, Action::Unappear).await,
         Command::List => {
-            match db.smembers(msg.chat.id.0).await {
-                Ok(subs) => {
+            let result: RedisResult<(Vec<Subscription>, Vec<Subscription>)> = redis::pipe()
+                .smembers(Action::Subscribe.chat_key(msg.chat.id))
+                .smembers(Action::Appear.chat_key(msg.chat.id))
+                .query_async(&mut db).await;
+            match result {
+                Ok((subs, appearances)) => {
                     let sub_list = fmt_subscriptions(&subs);
-                    if sub_list.is_empty() {
-                        bot.send_message(msg.chat.id, escape("You have no subscriptions.\nUse the /sub command to add new subscriptions.")).await?
+                    let mut text = if sub_list.is_empty() {
+                        lang.markdown("list.empty", &[])
                     } else {
-                        bot.send_message(msg.chat.id, format!("Your subscriptions:\n{sub_list}")).await?
+                        lang.markdown("list.subscriptions", &[("subs", &sub_list)])
+                    };
+                    if !appearances.is_empty() {
+                        text += "\n\n";
+                        text += &lang.markdown("list.appearances", &[("subs", &fmt_subscriptions(&appearances))]);
                     }
+                    bot.send_message(msg.chat.id, text).await?
                 },
-                Err(e) => bot.send_message(msg.chat.id, format!("Database error: {}", escape(&e.to_string()))).await?
+                Err(e) => bot.send_message(msg.chat.id, database_error(lang, &e)).await?
             }
         }
         Command::Platform => {
             let platforms = Platform::iter().enumerate()
                 .map(|(i, p)| format!("{}\\. {p}", i + 1)).collect::<Vec<_>>().join("\n");
-            bot.send_message(msg.chat.id, format!("Supported platforms:\n{platforms}")).await?
+            bot.send_message(msg.chat.id, lang.markdown("platforms", &[("platforms", &platforms)])).await?
         }
+        Command::Set(args) => return process_setting(&bot, &msg, &mut db, lang, args).await,
+        Command::Template(args) => return process_template(&bot, &msg, &mut db, lang, args).await,
+        Command::Language(code) => return process_language(&bot, &msg, &mut db, lang, code).await,
+        Command::Webhook(args) => return process_webhook(&bot, &msg, &mut db, lang, args).await,
+        Command::Feed(args) => return process_feed(&bot, &msg, &mut db, lang, args).await
     };
     Ok(())
 }
@@ -0,0 +1,341 @@
+use std::{collections::HashMap, time::Duration};
+
+use redis::{aio::MultiplexedConnection, RedisResult};
+use strum::IntoEnumIterator;
+use teloxide::{
+    prelude::Requester,
+    sugar::request::RequestLinkPreviewExt,
+    types::{ChatId, Me, Message},
+    utils::{command::BotCommands, markdown::escape},
+    RequestError
+};
+use tokio::time;
+use url::Url;
+
+use crate::{
+    access,
+    apis::{self, TWITTER_API},
+    chats,
+    config,
+    log_utils::LogResult,
+    metrics,
+    platform::Platform,
+    schema,
+    shutdown,
+    subscription::Subscription,
+    watcher::{
+        self,
+        sinks::{
+            discord::DiscordSink,
+            matrix::MatrixSink,
+            targets::{self, Target}
+        }
+    },
+    Bot
+};
+
+/// Commands only available to the bot owner
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase")]
+pub enum OwnerCommand {
+    /// Manage the Discord and Matrix notification targets, or list them if no argument is given.
+    /// /target discord <name> <webhook_url>
+    /// /target matrix <name> <homeserver_url> <room_id> <access_token>
+    /// /target bind|unbind <name> <url> ...
+    /// /target del <name>
+    Target(String),
+    /// Show the number of chats and subscriptions, the running lives and the API error rates
+    Stats,
+    /// Send a message to every subscribed chat.
+    /// /broadcast <text>
+    Broadcast(String),
+    /// Remove the subscriptions of a chat and ignore its messages.
+    /// /ban <chat_id>
+    Ban(String),
+    /// Accept the messages of a banned chat again.
+    /// /unban <chat_id>
+    Unban(String),
+    /// List the chats subscribed to the user of the URL.
+    /// /chats <url>
+    Chats(String),
+    /// Read ENV_FILE again and apply the new configuration and Twitter credentials
+    Reload,
+    /// Create a single-use invite link for the invite-only mode, valid for a week
+    Invite,
+    /// Allow a chat to use the bot in the invite-only mode.
+    /// /allow <chat_id>
+    Allow(String),
+    /// Forbid a chat to use the bot in the invite-only mode.
+    /// /disallow <chat_id>
+    Disallow(String)
+}
+
+/// Delay between two messages of a broadcast, below the limit of 30 messages per second of the Bot API
+const BROADCAST_INTERVAL: Duration = Duration::from_millis(50);
+
+pub fn is_owner(msg: Message) -> bool {
+    msg.from.is_some_and(|user| config::var("BOT_OWNER").is_ok_and(|owner| owner == user.id.to_string()))
+}
+
+/// Send a plain text message to the owner, if `BOT_OWNER` is set
+pub async fn notify(bot: &Bot, text: &str) {
+    let Ok(owner) = config::var("BOT_OWNER") else {
+        return;
+    };
+    bot.send_message(owner, escape(text)).await.log_ok("Failed to notify the owner");
+}
+
+async fn list_targets(db: &mut MultiplexedConnection) -> RedisResult<String> {
+    let targets = targets::list(db).await?;
+    if targets.is_empty() {
+        return Ok("No targets".to_owned());
+    }
+    Ok(targets.into_iter().map(|(name, target, count)| match target {
+        Some(target) => format!("{name} ({}): {count} subscriptions", target.kind()),
+        None => format!("{name} (invalid): {count} subscriptions")
+    }).collect::<Vec<_>>().join("\n"))
+}
+
+async fn parse_subscriptions(urls: &[&str]) -> Result<Vec<Subscription>, String> {
+    let mut subs = vec![];
+    for url in urls {
+        subs.push(Subscription::from_url(url.to_string()).await.map_err(|e| format!("{url}: {e}"))?);
+    }
+    Ok(subs)
+}
+
+async fn process_target(
+    bot: &Bot, msg: &Message, db: &mut MultiplexedConnection, args: String
+) -> Result<(), RequestError> {
+    let args: Vec<_> = args.split_whitespace().collect();
+    let target = match args[..] {
+        ["discord", name, webhook_url] => Some((name, webhook_url.parse::<Url>().map(|url| {
+            Target::Discord(DiscordSink { webhook_url: url.to_string() })
+        }))),
+        ["matrix", name, homeserver, room_id, access_token] => Some((name, homeserver.parse::<Url>().map(|url| {
+            Target::Matrix(MatrixSink {
+                homeserver: url.to_string(),
+                room_id: room_id.to_owned(),
+                access_token: access_token.to_owned()
+            })
+        }))),
+        _ => None
+    };
+    let result = match (target, &args[..]) {
+        (Some((name, Ok(target))), _) => {
+            // keep the webhook URL and access token out of the chat history
+            bot.delete_message(msg.chat.id, msg.id).await.log_ok("Failed to delete target command");
+            targets::add(db, name, &target).await.map(|()| format!("Target {name} saved"))
+        }
+        (Some((_, Err(e))), _) => Ok(format!("Invalid URL: {e}")),
+        (None, []) => list_targets(db).await,
+        (None, ["del", name]) => targets::remove(db, name).await.map(|removed| match removed {
+            true => format!("Target {name} removed"),
+            false => format!("Unknown target: {name}")
+        }),
+        (None, [action @ ("bind" | "unbind"), name, urls @ ..]) if !urls.is_empty() => {
+            match (targets::exists(db, name).await, parse_subscriptions(urls).await) {
+                (Ok(false), _) => Ok(format!("Unknown target: {name}")),
+                (Ok(true), Err(e)) => Ok(e),
+                (Ok(true), Ok(subs)) if *action == "bind" => targets::bind(db, name, &subs).await
+                    .map(|()| format!("Bound {name} to {} subscriptions", subs.len())),
+                (Ok(true), Ok(subs)) => targets::unbind(db, name, &subs).await
+                    .map(|()| format!("Unbound {name} from {} subscriptions", subs.len())),
+                (Err(e), _) => Err(e)
+            }
+        }
+        _ => Ok(OwnerCommand::descriptions().to_string())
+    };
+    let text = result.unwrap_or_else(|e| format!("Database error: {e}"));
+    bot.send_message(msg.chat.id, escape(&text)).disable_link_preview(true).await?;
+    Ok(())
+}
+
+async fn stats(db: &mut MultiplexedConnection) -> RedisResult<String> {
+    let (subs, appearances): (HashMap<String, String>, usize) = redis::pipe()
+        .hgetall(schema::key("subs")).hlen(schema::key("appearances")).query_async(db).await?;
+    let chats = chats::subscribers(db).await?.len();
+    let banned = chats::banned(db).await?;
+    let mut lines = vec![format!("Chats: {chats} ({banned} banned)")];
+    for platform in Platform::iter() {
+        let prefix = format!("{platform}:");
+        let lives: Vec<_> = subs.iter().filter(|(sub, _)| sub.starts_with(&prefix)).map(|(_, live_id)| live_id)
+            .collect();
+        let running = lives.iter().filter(|live_id| !live_id.is_empty()).count();
+        lines.push(format!("{platform}: {} subscriptions, {running} live", lives.len()));
+    }
+    lines.push(format!("Appearances: {appearances} users"));
+    lines.push("API errors since the start:".to_owned());
+    for (platform, (requests, errors)) in metrics::api_errors() {
+        let rate = if requests == 0 { 0.0 } else { errors as f64 * 100.0 / requests as f64 };
+        lines.push(format!("{platform}: {errors}/{requests} ({rate:.1}%)"));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Send the text to every subscribed chat which isn't banned, then tell the owner how many messages were sent
+async fn broadcast(bot: Bot, mut db: MultiplexedConnection, chats: Vec<String>, text: String) {
+    let (mut sent, mut failed) = (0, 0);
+    let total = chats.len();
+    for (index, chat_id) in chats.into_iter().enumerate() {
+        if shutdown::is_cancelled() {
+            let left = total - index;
+            let text = format!("Broadcast stopped by the shutdown, sent to {sent} chats, {failed} failed, {left} left");
+            notify(&bot, &text).await;
+            return;
+        }
+        if let Ok(chat) = chat_id.parse().map(ChatId) {
+            if chats::is_banned(&mut db, chat).await.unwrap_or(false) {
+                continue;
+            }
+        }
+        let mut result = bot.send_message(chat_id.clone(), escape(&text)).await;
+        if let Err(RequestError::RetryAfter(seconds)) = result {
+            time::sleep(seconds.duration()).await;
+            result = bot.send_message(chat_id.clone(), escape(&text)).await;
+        }
+        match result.log_ok(&format!("Failed to broadcast to {chat_id}")) {
+            Some(_) => sent += 1,
+            None => failed += 1
+        }
+        time::sleep(BROADCAST_INTERVAL).await;
+    }
+    notify(&bot, &format!("Broadcast sent to {sent} chats, {failed} failed")).await;
+}
+
+fn parse_chat_id(arg: &str) -> Option<ChatId> {
+    arg.trim().parse().ok().map(ChatId)
+}
+
+async fn ban(db: &mut MultiplexedConnection, arg: &str) -> RedisResult<String> {
+    let Some(chat_id) = parse_chat_id(arg) else {
+        return Ok(OwnerCommand::descriptions().to_string());
+    };
+    if config::var("BOT_OWNER").is_ok_and(|owner| owner == chat_id.to_string()) {
+        return Ok("The owner can't be banned".to_owned());
+    }
+    let removed = chats::ban(db, chat_id).await?;
+    Ok(format!("Banned {chat_id} and removed {removed} subscriptions"))
+}
+
+async fn unban(db: &mut MultiplexedConnection, arg: &str) -> RedisResult<String> {
+    let Some(chat_id) = parse_chat_id(arg) else {
+        return Ok(OwnerCommand::descriptions().to_string());
+    };
+    Ok(match chats::unban(db, chat_id).await? {
+        true => format!("Unbanned {chat_id}"),
+        false => format!("{chat_id} is not banned")
+    })
+}
+
+async fn list_chats(db: &mut MultiplexedConnection, url: &str) -> RedisResult<String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Ok(OwnerCommand::descriptions().to_string());
+    }
+    let sub = match Subscription::from_url(url.to_owned()).await {
+        Ok(sub) => sub,
+        Err(e) => return Ok(format!("{url}: {e}"))
+    };
+    let (chats, appearances) = chats::subscribers_of(db, &sub).await?;
+    let name = format!("{}: {}", sub.platform, sub.user.username);
+    let mut lines = vec![format!("{name}: {} chats", chats.len())];
+    lines.extend(chats);
+    if !appearances.is_empty() {
+        lines.push(format!("Appearances of {name}: {} chats", appearances.len()));
+        lines.extend(appearances);
+    }
+    Ok(lines.join("\n"))
+}
+
+async fn invite(db: &mut MultiplexedConnection, me: &Me) -> RedisResult<String> {
+    let code = access::invite(db).await?;
+    let mut text = format!("Invite link, valid for a week: https://t.me/{}?start={code}", me.username());
+    if !access::invite_only() {
+        text += "\nINVITE_ONLY is not set, anyone can use the bot";
+    }
+    Ok(text)
+}
+
+async fn allow(db: &mut MultiplexedConnection, arg: &str, allowed: bool) -> RedisResult<String> {
+    let Some(chat_id) = parse_chat_id(arg) else {
+        return Ok(OwnerCommand::descriptions().to_string());
+    };
+    if allowed {
+        access::allow(db, chat_id).await?;
+        return Ok(format!("Allowed {chat_id}"));
+    }
+    Ok(match access::disallow(db, chat_id).await? {
+        true => format!("Disallowed {chat_id}"),
+        false => format!("{chat_id} is not allowed")
+    })
+}
+
+/// Load the env file again and apply what can be changed without a restart
+async fn reload() -> String {
+    let Some(path) = config::env_file() else {
+        return "ENV_FILE is not set".to_owned();
+    };
+    let changed = match config::load() {
+        Ok(changed) => changed,
+        Err(e) => return format!("Failed to read {path}: {e}")
+    };
+    let credentials_changed = changed.iter().any(|key| key == "TWITTER_AUTH_TOKEN" || key == "TWITTER_CSRF_TOKEN");
+    // accounts rejected by Twitter are tried again, e.g. once they have been unlocked
+    let enabled = match TWITTER_API.get() {
+        Some(api) if !credentials_changed => api.accounts().enable(),
+        _ => 0
+    };
+    if changed.is_empty() && enabled == 0 {
+        return "Nothing changed".to_owned();
+    }
+    let mut lines = vec![];
+    if !changed.is_empty() {
+        watcher::reload();
+        lines.push(format!("Reloaded {}", changed.join(", ")));
+    }
+    if enabled > 0 {
+        lines.push(format!("Enabled {enabled} disabled Twitter accounts again"));
+    }
+    if credentials_changed {
+        match (TWITTER_API.get(), apis::twitter_credentials()) {
+            (Some(api), Ok(credentials)) => {
+                api.set_credentials(&credentials);
+                lines.push(format!("Using {} Twitter accounts", credentials.len()));
+            }
+            (None, Ok(_)) => (),
+            (_, Err(e)) => lines.push(format!("Invalid Twitter credentials: {e}"))
+        }
+    }
+    let restart: Vec<_> = changed.iter().filter(|key| config::RESTART_REQUIRED.contains(&key.as_str())).cloned()
+        .collect();
+    if !restart.is_empty() {
+        lines.push(format!("Restart to apply {}", restart.join(", ")));
+    }
+    lines.join("\n")
+}
+
+pub async fn owner_command_handler(
+    bot: Bot, msg: Message, me: Me, cmd: OwnerCommand, mut db: MultiplexedConnection
+) -> Result<(), RequestError> {
+    let result = match cmd {
+        OwnerCommand::Target(args) => return process_target(&bot, &msg, &mut db, args).await,
+        OwnerCommand::Stats => stats(&mut db).await,
+        OwnerCommand::Broadcast(text) if text.trim().is_empty() => Ok(OwnerCommand::descriptions().to_string()),
+        OwnerCommand::Broadcast(text) => chats::subscribers(&mut db).await.map(|chats| {
+            let count = chats.len();
+            shutdown::spawn(broadcast(bot.clone(), db.clone(), chats.into_iter().collect(), text.trim().to_owned()));
+            format!("Broadcasting to {count} chats")
+        }),
+        OwnerCommand::Ban(arg) => ban(&mut db, &arg).await,
+        OwnerCommand::Unban(arg) => unban(&mut db, &arg).await,
+        OwnerCommand::Chats(url) => list_chats(&mut db, &url).await,
+        OwnerCommand::Reload => Ok(reload().await),
+        OwnerCommand::Invite => invite(&mut db, &me).await,
+        OwnerCommand::Allow(arg) => allow(&mut db, &arg, true).await,
+        OwnerCommand::Disallow(arg) => allow(&mut db, &arg, false).await
+    };
+    let text = result.unwrap_or_else(|e| format!("Database error: {e}"));
+    bot.send_message(msg.chat.id, escape(&text)).disable_link_preview(true).await?;
+    Ok(())
+}
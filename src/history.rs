@@ -0,0 +1,131 @@
+use chrono::{DateTime, Timelike, Utc};
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisResult};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    schema,
+    subscription::Subscription,
+    watcher::sinks::{EventKind, LiveEvent}
+};
+
+/// Sessions kept in the history of a subscription
+const LENGTH: isize = 50;
+/// Time in seconds a session is kept after its last event
+const TTL: i64 = 90 * 24 * 3600;
+/// Hours around the start time of a live of the history during which a subscription is usually live
+const ACTIVE_HOURS: i32 = 1;
+
+/// Live session of the history, built from the live events of the watcher
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub platform: String,
+    pub user_id: String,
+    pub username: String,
+    pub creator: String,
+    pub live_id: String,
+    pub title: String,
+    pub url: String,
+    pub profile_url: Option<String>,
+    pub cover: Option<String>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// Time of the last event of the session
+    pub updated: i64
+}
+
+impl Session {
+    fn apply(session: Option<Self>, event: &LiveEvent) -> Self {
+        let start_time = event.start_time.or(session.as_ref().and_then(|s| s.start_time));
+        Self {
+            platform: event.platform.to_owned(),
+            user_id: event.user_id.clone(),
+            username: event.username.clone(),
+            creator: event.creator.clone(),
+            live_id: event.live_id.clone(),
+            title: event.title.clone(),
+            url: event.url.clone(),
+            profile_url: event.profile_url.clone(),
+            cover: event.cover.clone(),
+            start_time: start_time.or(matches!(event.event, EventKind::Start).then_some(event.time)),
+            end_time: matches!(event.event, EventKind::End).then_some(event.time),
+            updated: event.time
+        }
+    }
+
+    /// Time the session is sorted by
+    pub fn time(&self) -> i64 {
+        self.start_time.unwrap_or(self.updated)
+    }
+}
+
+fn history_key(sub: &Subscription) -> String {
+    schema::key(format!("history:{}", sub.to_db_string()))
+}
+
+fn session_key(platform: &str, live_id: &str) -> String {
+    schema::key(format!("session:{platform}:{live_id}"))
+}
+
+/// Update the session of the event in the history of the subscription
+pub async fn record(db: &mut MultiplexedConnection, sub: &Subscription, event: &LiveEvent) -> RedisResult<()> {
+    let key = session_key(event.platform, &event.live_id);
+    let session: Option<String> = db.get(&key).await?;
+    let session = Session::apply(session.and_then(|s| serde_json::from_str(&s).ok()), event);
+    let history = history_key(sub);
+    redis::pipe().atomic()
+        .set_ex(&key, serde_json::to_string(&session).expect("Sessions are serializable"), TTL as u64)
+        .zadd(&history, &session.live_id, session.time())
+        .zremrangebyrank(&history, 0, -LENGTH - 1)
+        .exec_async(db).await
+}
+
+/// Whether a live of the history started around the same hour of the day as `time`, the start times being the scores
+/// of the history. Subscriptions without history are considered active, so they are polled normally until they get one.
+pub async fn usually_live(db: &mut MultiplexedConnection, sub: &Subscription, time: DateTime<Utc>) -> RedisResult<bool> {
+    let start_times: Vec<(String, i64)> = db.zrange_withscores(history_key(sub), 0, -1).await?;
+    let start_times: Vec<_> = start_times.into_iter().map(|(_, time)| time).collect();
+    Ok(start_times.is_empty() || live_around(&start_times, time))
+}
+
+/// Whether one of the times is within [`ACTIVE_HOURS`] hours of the hour of the day of `time`
+fn live_around(start_times: &[i64], time: DateTime<Utc>) -> bool {
+    start_times.iter().filter_map(|t| DateTime::from_timestamp(*t, 0)).any(|start_time| {
+        let distance = (start_time.hour() as i32 - time.hour() as i32).rem_euclid(24);
+        distance.min(24 - distance) <= ACTIVE_HOURS
+    })
+}
+
+/// Most recent sessions of the subscriptions, latest first
+pub async fn sessions(db: &mut MultiplexedConnection, subs: &[Subscription]) -> RedisResult<Vec<Session>> {
+    let mut sessions = vec![];
+    for sub in subs {
+        let live_ids: Vec<String> = db.zrevrange(history_key(sub), 0, LENGTH - 1).await?;
+        if live_ids.is_empty() {
+            continue;
+        }
+        let keys: Vec<_> = live_ids.iter().map(|id| session_key(sub.platform.short_name(), id)).collect();
+        let values: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query_async(db).await?;
+        // sessions expire before the history is trimmed if the subscription is rarely live
+        sessions.extend(values.into_iter().flatten().filter_map(|s| serde_json::from_str::<Session>(&s).ok()));
+    }
+    sessions.sort_by_key(|s| -s.time());
+    sessions.truncate(LENGTH as usize);
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn live_around() {
+        // 2025-01-01 23:30 UTC
+        let start_times = [1735774200];
+        let time = |hour: i64| DateTime::from_timestamp(1735689600 + hour * 3600, 0).unwrap();
+        assert!(super::live_around(&start_times, time(23)));
+        assert!(super::live_around(&start_times, time(22)));
+        assert!(super::live_around(&start_times, time(24)));
+        assert!(!super::live_around(&start_times, time(21)));
+        assert!(!super::live_around(&start_times, time(12)));
+    }
+}
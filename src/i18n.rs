@@ -0,0 +1,148 @@
+use std::{collections::HashMap, fmt::Display};
+
+use lazy_static::lazy_static;
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisResult};
+use strum_macros::{Display, EnumIter, EnumString};
+use teloxide::{types::User, utils::markdown::escape};
+
+use crate::settings;
+
+/// Languages of the message catalog, identified by their language code
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "lowercase")]
+pub enum Language {
+    En,
+    Zh,
+    Ja
+}
+
+lazy_static! {
+    static ref CATALOG: HashMap<Language, HashMap<String, String>> = HashMap::from([
+        (Language::En, serde_json::from_str(include_str!("../locales/en.json")).expect("Invalid catalog: en")),
+        (Language::Zh, serde_json::from_str(include_str!("../locales/zh.json")).expect("Invalid catalog: zh")),
+        (Language::Ja, serde_json::from_str(include_str!("../locales/ja.json")).expect("Invalid catalog: ja"))
+    ]);
+}
+
+/// Field of the language in the `settings:<chat_id>` hash
+const FIELD: &str = "language";
+
+impl Language {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::En => "English",
+            Language::Zh => "中文",
+            Language::Ja => "日本語"
+        }
+    }
+
+    /// Language matching the IETF language tag of a Telegram user, English if it isn't supported
+    pub fn from_code(code: Option<&str>) -> Self {
+        code.and_then(|code| code.split('-').next()?.to_lowercase().parse().ok()).unwrap_or(Language::En)
+    }
+
+    /// Message of this language only, without falling back to English
+    pub fn find(&self, key: &str) -> Option<&'static str> {
+        CATALOG[self].get(key).map(String::as_str)
+    }
+
+    /// Raw message, e.g. a MarkdownV2 template
+    pub fn get<'a>(&self, key: &'a str) -> &'a str {
+        self.find(key).or_else(|| Language::En.find(key)).unwrap_or_else(|| {
+            log::error!("Missing message: {key}");
+            key
+        })
+    }
+
+    fn format(&self, key: &str, args: &[(&str, &(dyn Display + Sync))], literal: impl Fn(&str) -> String) -> String {
+        let mut text = self.get(key);
+        let mut output = String::new();
+        while let Some(start) = text.find('{') {
+            let Some(end) = text[start..].find('}').map(|end| start + end) else {
+                break;
+            };
+            output += &literal(&text[..start]);
+            match args.iter().find(|(name, _)| *name == &text[start + 1..end]) {
+                Some((_, value)) => output += &value.to_string(),
+                None => output += &literal(&text[start..=end])
+            }
+            text = &text[end + 1..];
+        }
+        output + &literal(text)
+    }
+
+    /// Plain text message with its `{name}` arguments filled in
+    pub fn text(&self, key: &str, args: &[(&str, &(dyn Display + Sync))]) -> String {
+        self.format(key, args, str::to_owned)
+    }
+
+    /// MarkdownV2 message, the arguments are inserted as they are and must be escaped by the caller
+    pub fn markdown(&self, key: &str, args: &[(&str, &(dyn Display + Sync))]) -> String {
+        self.format(key, args, escape)
+    }
+}
+
+/// Language of the chat, English if it has never been set
+pub async fn get(db: &mut MultiplexedConnection, chat_id: impl Display) -> Language {
+    match db.hget::<_, _, Option<String>>(settings::key(&chat_id), FIELD).await {
+        Ok(language) => Language::from_code(language.as_deref()),
+        Err(e) => {
+            log::error!("Failed to get language of {chat_id}: {e}");
+            Language::En
+        }
+    }
+}
+
+pub async fn set(db: &mut MultiplexedConnection, chat_id: impl Display, language: Language) -> RedisResult<()> {
+    db.hset(settings::key(chat_id), FIELD, language.to_string()).await
+}
+
+/// Language of the chat, initialized from the language of the Telegram user when it has never been set
+pub async fn resolve(db: &mut MultiplexedConnection, chat_id: impl Display, user: Option<&User>) -> Language {
+    let default = Language::from_code(user.and_then(|user| user.language_code.as_deref()));
+    match db.hget::<_, _, Option<String>>(settings::key(&chat_id), FIELD).await {
+        Ok(Some(language)) => Language::from_code(Some(&language)),
+        Ok(None) => {
+            let result: RedisResult<()> = db.hset_nx(settings::key(&chat_id), FIELD, default.to_string()).await;
+            if let Err(e) = result {
+                log::error!("Failed to save language of {chat_id}: {e}");
+            }
+            default
+        }
+        Err(e) => {
+            log::error!("Failed to get language of {chat_id}: {e}");
+            default
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    #[test]
+    fn catalogs() {
+        let keys = |language: &Language| {
+            CATALOG[language].keys().filter(|key| !key.starts_with("command.")).collect::<HashSet<_>>()
+        };
+        for language in Language::iter() {
+            assert_eq!(keys(&language), keys(&Language::En), "{language} does not match en");
+        }
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(Language::from_code(Some("zh-hans")), Language::Zh);
+        assert_eq!(Language::from_code(Some("pt-br")), Language::En);
+        assert_eq!(
+            Language::En.markdown("urls.invalid", &[("url", &"*a*"), ("error", &"(b)")]),
+            "*a*: (b)"
+        );
+        assert_eq!(Language::En.markdown("settings.usage", &[]), "Usage: /set <setting\\> <on\\|off\\>");
+        assert_eq!(Language::En.text("template.current", &[("template", &"{x}")]), "Current template:\n{x}");
+    }
+}
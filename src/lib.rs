@@ -0,0 +1,21 @@
+use teloxide::adaptors::DefaultParseMode;
+
+pub type Bot = DefaultParseMode<teloxide::Bot>;
+
+pub mod access;
+pub mod chats;
+pub mod config;
+pub mod handlers;
+pub mod history;
+pub mod i18n;
+pub mod metrics;
+pub mod platform;
+pub mod schema;
+pub mod server;
+pub mod settings;
+pub mod shutdown;
+pub mod subscription;
+pub mod templates;
+pub mod apis;
+pub mod watcher;
+pub mod log_utils;
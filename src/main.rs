@@ -1,62 +1,125 @@
 use std::{env, panic, process::exit};
 
-use handlers::{callback::callback_handler, command::{command_handler, Command}};
-use log::warn;
+use log::{error, info, warn};
+use redis::aio::MultiplexedConnection;
+use strum::IntoEnumIterator;
 use teloxide::{
-    adaptors::DefaultParseMode,
-    dispatching::UpdateFilterExt,
-    filter_command,
+    payloads::SetMyCommandsSetters,
+    net,
     prelude::{Dispatcher, LoggingErrorHandler, Request, Requester, RequesterExt},
-    types::{Message, ParseMode, Update},
-    utils::{command::BotCommands, markdown::{code_block, escape}},
+    types::{BotCommandScope, ChatId, ParseMode, Recipient},
+    update_listeners::webhooks,
+    utils::{command::BotCommands, markdown::code_block},
     RequestError
 };
+use telescope::{
+    config,
+    handlers::{self, command::localized_commands, owner::OwnerCommand},
+    i18n::Language,
+    schema,
+    server,
+    shutdown,
+    watcher::{sinks::webhook, watch}
+};
 use tokio::{runtime::Handle, task::block_in_place};
-use watcher::watch;
-
-type Bot = DefaultParseMode<teloxide::Bot>;
 
-mod handlers;
-mod platform;
-mod subscription;
-mod apis;
-mod watcher;
-mod log_utils;
+/// `telescope migrate [--dry-run]`: upgrade the database to the current schema, or only list the changes
+async fn migrate(db: &mut MultiplexedConnection, dry_run: bool) {
+    let migration = schema::migrate(db, dry_run).await.expect("Failed to migrate the database");
+    let Some(from) = migration.from else {
+        println!("The database is empty, it is at version {} now", schema::VERSION);
+        return;
+    };
+    if from >= schema::VERSION {
+        println!("The database is at version {from}, there is nothing to migrate");
+        return;
+    }
+    for (name, new_name) in &migration.renamed {
+        println!("Rename {name} to {new_name}");
+    }
+    let renamed = migration.renamed.len();
+    match dry_run {
+        true => println!("Dry run: {renamed} keys would be renamed from version {from} to {}", schema::VERSION),
+        false => println!("Migrated {renamed} keys from version {from} to {}", schema::VERSION)
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), RequestError> {
     pretty_env_logger::init();
-    let bot = teloxide::Bot::from_env().parse_mode(ParseMode::MarkdownV2);
+    config::load().expect("Failed to read ENV_FILE");
+    if config::var("WEBHOOK_URL").is_ok() && webhook::global_secret().is_none() {
+        error!("WEBHOOK_SECRET is required when WEBHOOK_URL is set");
+        exit(1);
+    }
     const REDIS_ERROR_MSG: &str = "Failed to connect to redis server";
-    let client = redis::Client::open("redis://127.0.0.1/").expect(REDIS_ERROR_MSG);
-    let db = client.get_multiplexed_async_connection().await.expect(REDIS_ERROR_MSG);
+    let redis_url = config::var("REDIS_URL").unwrap_or("redis://127.0.0.1/".to_owned());
+    let client = redis::Client::open(redis_url).expect(REDIS_ERROR_MSG);
+    let mut db = client.get_multiplexed_async_connection().await.expect(REDIS_ERROR_MSG);
+    if env::args().nth(1).as_deref() == Some("migrate") {
+        migrate(&mut db, env::args().any(|arg| arg == "--dry-run")).await;
+        return Ok(());
+    }
+    let version = schema::init(&mut db).await.expect(REDIS_ERROR_MSG);
+    if version < schema::VERSION {
+        error!("The database is at schema version {version}, run `telescope migrate` to upgrade it");
+        exit(1);
+    } else if version > schema::VERSION {
+        error!("The database is at schema version {version}, written by a newer version of the bot");
+        exit(1);
+    }
+    let token = config::var("TELOXIDE_TOKEN").expect("TELOXIDE_TOKEN is required");
+    let bot = teloxide::Bot::with_client(token, net::client_from_env()).parse_mode(ParseMode::MarkdownV2);
     let panic_bot = bot.clone();
     let hook = panic::take_hook();
+    // a panic only ends its task, the watchdog restarts the watchers, so it is only reported
     panic::set_hook(Box::new(move |info| {
         hook(info);
-        block_in_place(|| Handle::current().block_on(
-            panic_bot.send_message(env::var("BOT_OWNER").ok()?, code_block(format!("{info}").as_str())).send()
+        let Ok(handle) = Handle::try_current() else {
+            return;
+        };
+        block_in_place(|| handle.block_on(
+            panic_bot.send_message(config::var("BOT_OWNER").ok()?, code_block(format!("{info}").as_str())).send()
         ).ok());
-        exit(1);
     }));
-    bot.set_my_commands(Command::bot_commands()).await.expect("Loading bot commands failed.");
-    let handler = dptree::entry().branch(
-        Update::filter_message().branch(
-            filter_command::<Command, _>().endpoint(command_handler)
-        ).endpoint(async |bot: Bot, msg: Message|
-            bot.send_message(msg.chat.id, escape("Sorry, I don't understand.")).await.and(Ok(()))
-        )
-    ).branch(
-        Update::filter_callback_query().endpoint(callback_handler)
-    );
+    bot.set_my_commands(localized_commands(Language::En)).await.expect("Loading bot commands failed.");
+    for language in Language::iter().filter(|language| *language != Language::En) {
+        bot.set_my_commands(localized_commands(language)).language_code(language.to_string()).await
+            .expect("Loading bot commands failed.");
+    }
+    if let Some(owner) = config::var("BOT_OWNER").ok().and_then(|owner| owner.parse().ok()) {
+        let commands = [localized_commands(Language::En), OwnerCommand::bot_commands()].concat();
+        bot.set_my_commands(commands).scope(BotCommandScope::Chat { chat_id: Recipient::Id(ChatId(owner)) }).await
+            .expect("Loading bot commands failed.");
+    }
     watch(db.clone(), bot.clone());
-    Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![db])
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handlers::schema())
+        .dependencies(dptree::deps![db.clone()])
         .default_handler(async |update| warn!("Unhandled update: {update:?}"))
         .error_handler(LoggingErrorHandler::with_custom_text("Dispatcher error"))
-        .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
+        .build();
+    let token = dispatcher.shutdown_token();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        info!("Shutting down");
+        shutdown::cancel();
+        match token.shutdown() {
+            Ok(stopped) => stopped.await,
+            Err(_) => info!("The dispatcher isn't running")
+        }
+    });
+    match server::telegram_webhook() {
+        Some(options) => {
+            // the webhook is deleted once the dispatcher stops the listener, which also stops the HTTP server
+            let (listener, stop, router) = webhooks::axum_to_router(bot, options).await?;
+            server::serve(server::router(db).merge(router), stop).await;
+            dispatcher.dispatch_with_listener(listener, LoggingErrorHandler::with_custom_text("Webhook error")).await;
+        }
+        None => {
+            server::serve(server::router(db), shutdown::cancelled()).await;
+            dispatcher.dispatch().await;
+        }
+    }
+    shutdown::drain().await;
     Ok(())
 }
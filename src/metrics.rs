@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use lazy_static::lazy_static;
+use prometheus::{
+    core::Collector,
+    register_histogram_vec,
+    register_int_counter,
+    register_int_counter_vec,
+    register_int_gauge_vec,
+    HistogramVec,
+    IntCounter,
+    IntCounterVec,
+    IntGaugeVec,
+    TextEncoder
+};
+use redis::RedisResult;
+
+lazy_static! {
+    pub static ref API_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "telescope_api_requests_total",
+        "Platform API requests by status code, `error` if no response was received",
+        &["platform", "status"]
+    ).unwrap();
+    pub static ref API_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "telescope_api_request_duration_seconds",
+        "Duration of the platform API requests",
+        &["platform"]
+    ).unwrap();
+    pub static ref WATCHER_CYCLE_DURATION: HistogramVec = register_histogram_vec!(
+        "telescope_watcher_cycle_duration_seconds",
+        "Duration of the checks of the subscriptions of a platform",
+        &["platform"],
+        vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0]
+    ).unwrap();
+    pub static ref LIVES_DETECTED: IntCounterVec = register_int_counter_vec!(
+        "telescope_lives_detected_total",
+        "Lives detected by the watcher",
+        &["platform"]
+    ).unwrap();
+    pub static ref NOTIFICATIONS: IntCounterVec = register_int_counter_vec!(
+        "telescope_notifications_total",
+        "Notifications by channel (telegram, webhook, target) and result (sent, failed)",
+        &["channel", "result"]
+    ).unwrap();
+    pub static ref SUBSCRIPTIONS: IntGaugeVec = register_int_gauge_vec!(
+        "telescope_subscriptions",
+        "Subscriptions polled by the watcher",
+        &["platform"]
+    ).unwrap();
+    pub static ref REDIS_ERRORS: IntCounter = register_int_counter!(
+        "telescope_redis_errors_total",
+        "Failed Redis commands of the watcher"
+    ).unwrap();
+    pub static ref WBI_KEY_REFRESHES: IntCounterVec = register_int_counter_vec!(
+        "telescope_wbi_key_refreshes_total",
+        "Refreshes of the Bilibili WBI signing key by result (success, failure)",
+        &["result"]
+    ).unwrap();
+}
+
+/// Count a notification by its result
+pub fn notification(channel: &str, sent: bool) {
+    NOTIFICATIONS.with_label_values(&[channel, if sent { "sent" } else { "failed" }]).inc();
+}
+
+/// Requests to each platform API since the start, and how many of them failed without a response or with an
+/// unsuccessful status
+pub fn api_errors() -> BTreeMap<String, (u64, u64)> {
+    let mut counts: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    for metric in API_REQUESTS.collect().iter().flat_map(|family| family.get_metric()) {
+        let label = |name| metric.get_label().iter().find(|label| label.name() == name).map(|label| label.value());
+        let count = metric.get_counter().get_value() as u64;
+        let (requests, errors) = counts.entry(label("platform").unwrap_or_default().to_owned()).or_default();
+        *requests += count;
+        if !label("status").is_some_and(|status| status.starts_with('2')) {
+            *errors += count;
+        }
+    }
+    counts
+}
+
+pub trait CountError {
+    /// Count the error of a Redis command in [`REDIS_ERRORS`]
+    fn count_err(self) -> Self;
+}
+
+impl<T> CountError for RedisResult<T> {
+    fn count_err(self) -> Self {
+        self.inspect_err(|_| REDIS_ERRORS.inc())
+    }
+}
+
+/// Metrics in the Prometheus text format
+pub fn render() -> String {
+    TextEncoder::new().encode_to_string(&prometheus::gather()).unwrap_or_else(|e| {
+        log::error!("Failed to encode metrics: {e}");
+        String::new()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        API_REQUESTS.with_label_values(&["twitter", "200"]).inc();
+        notification("telegram", false);
+        let metrics = super::render();
+        assert!(metrics.contains("telescope_api_requests_total{platform=\"twitter\",status=\"200\"}"));
+        assert!(metrics.contains("telescope_notifications_total{channel=\"telegram\",result=\"failed\"} 1"));
+    }
+
+    #[test]
+    fn api_errors() {
+        API_REQUESTS.with_label_values(&["test", "200"]).inc_by(3);
+        API_REQUESTS.with_label_values(&["test", "503"]).inc();
+        API_REQUESTS.with_label_values(&["test", "error"]).inc();
+        assert_eq!(super::api_errors()["test"], (5, 2));
+    }
+}
@@ -2,7 +2,7 @@ use lazy_regex::{lazy_regex, Lazy};
 use regex::Regex;
 use strum_macros::{Display, EnumIter, EnumString};
 
-use crate::apis::{get_bilibili_api, get_twitter_api};
+use crate::{apis::{get_bilibili_api, get_twitter_api}, subscription::UrlError};
 
 #[derive(Clone)]
 pub struct User {
@@ -22,17 +22,28 @@ impl Platform {
     const TWITTER_USERNAME: Lazy<Regex> = lazy_regex!(r"^/(?P<username>\w{4,15})/?$");
     const BILIBILI_ROOM_ID: Lazy<Regex> = lazy_regex!(r"^/(?P<room_id>\d+)/?$");
 
-    pub async fn parse_user(self: &Self, path: &str) -> Option<User> {
+    /// Short name used to refer to the platform in commands
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Platform::TwitterSpace => "twitter",
+            Platform::BilibiliLive => "bilibili"
+        }
+    }
+
+    pub async fn parse_user(self: &Self, path: &str) -> Result<User, UrlError> {
+        let api_error = |e| UrlError::Lookup(self.clone(), e);
         match self {
             Platform::TwitterSpace => {
-                let username = Self::TWITTER_USERNAME.captures(path)?["username"].to_owned();
-                let id = get_twitter_api().await.user_id(&username).await?;
-                Some(User { id, username })
+                let captures = Self::TWITTER_USERNAME.captures(path).ok_or(UrlError::InvalidUser(self.clone()))?;
+                let username = captures["username"].to_owned();
+                let id = get_twitter_api().await.user_id(&username).await.map_err(api_error)?;
+                Ok(User { id, username })
             }
             Platform::BilibiliLive => {
-                let id = Self::BILIBILI_ROOM_ID.captures(path)?["room_id"].to_owned();
-                let username = get_bilibili_api().await.username(&id).await?;
-                Some(User { id, username })
+                let captures = Self::BILIBILI_ROOM_ID.captures(path).ok_or(UrlError::InvalidUser(self.clone()))?;
+                let id = captures["room_id"].to_owned();
+                let username = get_bilibili_api().await.username(&id).await.map_err(api_error)?;
+                Ok(User { id, username })
             }
         }
     }
@@ -0,0 +1,129 @@
+//! Layout of the Redis database: the prefix of the keys, the version of their schema, and the migration of the data
+//! written by older versions of the bot
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display
+};
+
+use lazy_static::lazy_static;
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisResult};
+
+use crate::{config, subscription::Subscription};
+
+/// Version of the schema written by this version of the bot
+pub const VERSION: u32 = 2;
+/// Key holding the version of the schema, under the prefix
+const VERSION_KEY: &str = "schema_version";
+
+lazy_static! {
+    /// Prefix of every key, set by `REDIS_KEY_PREFIX`, `telescope:` by default
+    static ref PREFIX: String = config::var("REDIS_KEY_PREFIX").unwrap_or("telescope:".to_owned());
+}
+
+/// Key of the name under the prefix
+pub fn key(name: impl Display) -> String {
+    format!("{}{name}", *PREFIX)
+}
+
+/// Keys holding a single value, named the same in every version
+const SINGLETONS: [&str; 6] = ["subs", "appearances", "feeds", "targets", "banned", "allowed"];
+
+/// Keys of the version 1, which has no prefix, with their name in the version 2 without the prefix. Only the keys
+/// reachable from the subscriptions, chats and targets known to the bot are listed, other applications may share the
+/// database. The keys kept for a short time, like the pending confirmations and the invite codes, are left to expire.
+async fn keys_v1(db: &mut MultiplexedConnection) -> RedisResult<Vec<(String, String)>> {
+    let same = |name: String| (name.clone(), name);
+    let mut names: Vec<_> = SINGLETONS.iter().map(|name| same(name.to_string())).collect();
+    let (subs, appearances, feeds, targets, banned, allowed): SingletonValues = redis::pipe()
+        .hgetall("subs").hkeys("appearances").hvals("feeds").hkeys("targets").smembers("banned").smembers("allowed")
+        .query_async(db).await?;
+    let mut chats: BTreeSet<String> = feeds.into_iter().chain(banned).chain(allowed).collect();
+    for (name, live_id) in subs {
+        let Ok(sub) = name.parse::<Subscription>() else {
+            continue;
+        };
+        chats.extend(db.hkeys::<_, Vec<String>>(&name).await?);
+        let history: Vec<String> = db.zrange(format!("history:{name}"), 0, -1).await?;
+        names.extend(history.iter().map(|id| same(format!("session:{}:{id}", sub.platform.short_name()))));
+        if !live_id.is_empty() {
+            names.push(same(format!("live:{}:{live_id}", sub.platform)));
+        }
+        names.extend([same(format!("history:{name}")), same(format!("targets:{name}"))]);
+        names.push((name.clone(), format!("sub:{name}")));
+    }
+    for name in appearances {
+        chats.extend(db.smembers::<_, Vec<String>>(format!("appearances:{name}")).await?);
+        names.push(same(format!("appearances:{name}")));
+    }
+    names.extend(targets.into_iter().map(|name| same(format!("target:{name}"))));
+    for chat in chats {
+        names.extend(["appearances", "settings", "templates", "webhook"].map(|kind| same(format!("{kind}:{chat}"))));
+        names.push((chat.clone(), format!("chat:{chat}")));
+    }
+    Ok(names)
+}
+
+/// Values of `subs`, `appearances`, `feeds`, `targets`, `banned` and `allowed` in the version 1 which lead to other keys
+type SingletonValues = (
+    HashMap<String, String>,
+    Vec<String>,
+    Vec<String>,
+    Vec<String>,
+    Vec<String>,
+    Vec<String>
+);
+
+/// Version of the schema of the database, 1 for the keys without prefix written before it was stored,
+/// or `None` if the database is empty
+pub async fn stored_version(db: &mut MultiplexedConnection) -> RedisResult<Option<u32>> {
+    if let Some(version) = db.get(key(VERSION_KEY)).await? {
+        return Ok(Some(version));
+    }
+    let legacy: usize = db.exists(&SINGLETONS).await?;
+    Ok((legacy > 0).then_some(1))
+}
+
+/// Version of the schema of the database, which is written if the database is empty
+pub async fn init(db: &mut MultiplexedConnection) -> RedisResult<u32> {
+    if let Some(version) = stored_version(db).await? {
+        return Ok(version);
+    }
+    let _: () = db.set(key(VERSION_KEY), VERSION).await?;
+    Ok(VERSION)
+}
+
+/// Changes made by [`migrate`]
+pub struct Migration {
+    /// Version of the database before the migration, `None` if it was empty
+    pub from: Option<u32>,
+    /// Keys renamed, with their new name
+    pub renamed: Vec<(String, String)>
+}
+
+/// Upgrade the database to the current version of the schema at once, or only list the changes if `dry_run`
+pub async fn migrate(db: &mut MultiplexedConnection, dry_run: bool) -> RedisResult<Migration> {
+    let from = stored_version(db).await?;
+    let mut migration = Migration { from, renamed: vec![] };
+    if from == Some(1) {
+        let names: BTreeMap<_, _> = keys_v1(db).await?.into_iter().map(|(name, new_name)| (name, key(new_name)))
+            .filter(|(name, new_name)| name != new_name).collect();
+        let mut pipe = redis::pipe();
+        for name in names.keys() {
+            pipe.exists(name);
+        }
+        let exists: Vec<bool> = pipe.query_async(db).await?;
+        migration.renamed = names.into_iter().zip(exists).filter(|(_, exists)| *exists).map(|(names, _)| names)
+            .collect();
+    }
+    if dry_run || from.is_some_and(|version| version >= VERSION) {
+        return Ok(migration);
+    }
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    for (name, new_name) in &migration.renamed {
+        pipe.rename(name, new_name);
+    }
+    pipe.set(key(VERSION_KEY), VERSION).exec_async(db).await?;
+    Ok(migration)
+}
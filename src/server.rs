@@ -0,0 +1,67 @@
+use std::future::Future;
+
+use axum::{routing::get, Router};
+use redis::aio::MultiplexedConnection;
+use teloxide::{types::InputFile, update_listeners::webhooks::Options};
+use tokio::{net::TcpListener, task};
+use url::Url;
+
+use crate::{config, metrics, shutdown};
+
+pub mod calendar;
+pub mod feeds;
+pub mod health;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: MultiplexedConnection
+}
+
+/// Public base URL of the HTTP server, used in the links it gives out, configured by `PUBLIC_URL`
+pub fn public_url() -> Option<Url> {
+    let url = config::var("PUBLIC_URL").ok()?;
+    // without a trailing slash, joining paths would replace the last segment
+    format!("{}/", url.trim_end_matches('/')).parse().inspect_err(|e| log::error!("Invalid PUBLIC_URL: {e}")).ok()
+}
+
+/// Options of the Telegram webhook, receiving updates on the HTTP server instead of long polling,
+/// if `TELEGRAM_WEBHOOK_URL` is set
+pub fn telegram_webhook() -> Option<Options> {
+    let url: Url = config::var("TELEGRAM_WEBHOOK_URL").ok()?.parse().expect("Invalid TELEGRAM_WEBHOOK_URL");
+    let addr = config::var("LISTEN_ADDR").expect("LISTEN_ADDR is required by the Telegram webhook");
+    let mut options = Options::new(addr.parse().expect("Invalid LISTEN_ADDR"), url);
+    // the path differs from the one of the public URL behind a reverse proxy rewriting paths
+    if let Ok(path) = config::var("TELEGRAM_WEBHOOK_PATH") {
+        options = options.path(path);
+    }
+    // a random secret token is generated if none is given
+    if let Ok(secret) = config::var("TELEGRAM_WEBHOOK_SECRET") {
+        options = options.secret_token(secret);
+    }
+    if let Ok(certificate) = config::var("TELEGRAM_WEBHOOK_CERTIFICATE") {
+        options = options.certificate(InputFile::file(certificate));
+    }
+    Some(options)
+}
+
+pub fn router(db: MultiplexedConnection) -> Router {
+    Router::new()
+        .route("/feeds/{file}", get(feeds::chat_feed))
+        .route("/feeds/{platform}/{file}", get(feeds::subscription_feed))
+        .route("/metrics", get(async || metrics::render()))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .with_state(AppState { db })
+}
+
+/// Serve the router on `LISTEN_ADDR` until the shutdown future completes, if it is set
+pub async fn serve(
+    router: Router, shutdown: impl Future<Output = ()> + Send + 'static
+) -> Option<task::JoinHandle<()>> {
+    let addr = config::var("LISTEN_ADDR").ok()?;
+    let listener = TcpListener::bind(&addr).await.expect("Failed to bind LISTEN_ADDR");
+    log::info!("Listening on {addr}");
+    Some(shutdown::spawn(async move {
+        axum::serve(listener, router).with_graceful_shutdown(shutdown).await.expect("HTTP server error");
+    }))
+}
@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+
+use super::feeds::Feed;
+use crate::history::Session;
+
+/// Escape a text value, RFC 5545 section 3.3.11
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n").replace('\r', "")
+}
+
+fn date_time(timestamp: i64) -> Option<String> {
+    DateTime::<Utc>::from_timestamp(timestamp, 0).map(|time| time.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+/// Fold a content line into lines of at most 75 octets, RFC 5545 section 3.1
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded += "\r\n ";
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded + "\r\n"
+}
+
+impl Session {
+    /// Stable UID, so calendar clients replace the event when the session is updated
+    fn uid(&self) -> String {
+        format!("{}-{}@telescope", self.platform, self.live_id)
+    }
+
+    /// Lines of the VEVENT of the session, running lives ending at `now`
+    fn event(&self, now: i64) -> Option<Vec<String>> {
+        let start_time = self.start_time?;
+        let end_time = self.end_time.unwrap_or(now).max(start_time);
+        let mut description = self.url.clone();
+        if self.end_time.is_none() {
+            description = format!("Live now\n{description}");
+        }
+        Some(vec![
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:{}", self.uid()),
+            format!("DTSTAMP:{}", date_time(self.updated)?),
+            format!("LAST-MODIFIED:{}", date_time(self.updated)?),
+            format!("DTSTART:{}", date_time(start_time)?),
+            format!("DTEND:{}", date_time(end_time)?),
+            format!("SUMMARY:{}", escape_text(&format!("{}: {}", self.creator, self.title))),
+            format!("DESCRIPTION:{}", escape_text(&description)),
+            format!("URL:{}", self.url),
+            format!("STATUS:{}", if self.end_time.is_some() { "CONFIRMED" } else { "TENTATIVE" }),
+            "END:VEVENT".to_owned()
+        ])
+    }
+}
+
+impl Feed {
+    /// iCalendar of the sessions with a known start time
+    pub fn ics(&self, now: i64) -> String {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_owned(),
+            "VERSION:2.0".to_owned(),
+            "PRODID:-//Telescope//Telescope//EN".to_owned(),
+            "CALSCALE:GREGORIAN".to_owned(),
+            format!("X-WR-CALNAME:{}", escape_text(&self.title))
+        ];
+        lines.extend(self.sessions.iter().filter_map(|session| session.event(now)).flatten());
+        lines.push("END:VCALENDAR".to_owned());
+        lines.iter().map(|line| fold(line)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::feeds::tests::feed;
+
+    #[test]
+    fn ics() {
+        let mut feed = feed();
+        let mut running = feed.sessions[0].clone();
+        running.live_id = "67891".to_owned();
+        running.start_time = Some(1735740000);
+        running.end_time = None;
+        feed.sessions.insert(0, running);
+        let ics = feed.ics(1735743600);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("UID:bilibili-67890@telescope\r\n"));
+        assert!(ics.contains("DTSTART:20250101T120000Z\r\nDTEND:20250101T130000Z\r\n"));
+        assert!(ics.contains("SUMMARY:Telescope: <Test> & more\r\n"));
+        assert!(ics.contains("UID:bilibili-67891@telescope\r\n"));
+        assert!(ics.contains("DTSTART:20250101T140000Z\r\nDTEND:20250101T150000Z\r\n"));
+        assert!(ics.contains("STATUS:TENTATIVE\r\n"));
+        assert!(ics.lines().all(|line| line.len() <= 75));
+    }
+
+    #[test]
+    fn folding() {
+        assert_eq!(escape_text("a,b;c\\d\ne"), "a\\,b\\;c\\\\d\\ne");
+        let line = "あ".repeat(30);
+        let folded = fold(&line);
+        assert_eq!(folded.replace("\r\n ", ""), format!("{line}\r\n"));
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt::Display;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json
+};
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use redis::{aio::MultiplexedConnection, AsyncCommands, AsyncIter, RedisResult};
+use serde_json::{json, Value};
+use strum::IntoEnumIterator;
+use teloxide::types::ChatId;
+use url::Url;
+
+use super::{public_url, AppState};
+use crate::{
+    apis::{fmt_duration, fmt_time},
+    handlers::command::Action,
+    history::{self, Session},
+    platform::Platform,
+    schema,
+    settings,
+    subscription::Subscription
+};
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Atom,
+    Json,
+    Ics
+}
+
+impl Format {
+    /// Split a feed file name into its name and format
+    fn split(file: &str) -> Option<(&str, Self)> {
+        match file.rsplit_once('.')? {
+            (name, "atom") => Some((name, Format::Atom)),
+            (name, "json") => Some((name, Format::Json)),
+            (name, "ics") => Some((name, Format::Ics)),
+            _ => None
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Atom => "atom",
+            Format::Json => "json",
+            Format::Ics => "ics"
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn rfc3339(timestamp: i64) -> Option<String> {
+    DateTime::<Utc>::from_timestamp(timestamp, 0).map(|time| time.to_rfc3339())
+}
+
+impl Session {
+    fn entry_id(&self) -> String {
+        format!("urn:telescope:{}:{}", self.platform, self.live_id)
+    }
+
+    fn entry_title(&self) -> String {
+        format!("{}: {}", self.creator, self.title)
+    }
+
+    fn content_html(&self) -> String {
+        let time = |timestamp: Option<i64>| timestamp.and_then(|t| DateTime::from_timestamp(t, 0));
+        let mut html = String::new();
+        if let Some(start_time) = time(self.start_time) {
+            html += &format!("<p>Started: {}</p>", fmt_time(start_time));
+        }
+        match (time(self.start_time), time(self.end_time)) {
+            (Some(start_time), Some(end_time)) => {
+                html += &format!("<p>Ended: {} ({})</p>", fmt_time(end_time), fmt_duration(end_time - start_time));
+            }
+            (None, Some(end_time)) => html += &format!("<p>Ended: {}</p>", fmt_time(end_time)),
+            (_, None) => html += "<p>Live now</p>"
+        }
+        if let Some(cover) = &self.cover {
+            html += &format!("<p><img src=\"{}\"></p>", escape_xml(cover));
+        }
+        html
+    }
+}
+
+/// Feed of live sessions, latest first
+pub struct Feed {
+    pub id: String,
+    pub title: String,
+    /// Public URL of the feed without its extension
+    pub url: Option<Url>,
+    pub sessions: Vec<Session>
+}
+
+impl Feed {
+    fn url(&self, format: Format) -> Option<String> {
+        self.url.as_ref().map(|url| format!("{url}.{}", format.extension()))
+    }
+
+    pub fn atom(&self) -> String {
+        let updated = self.sessions.iter().map(|s| s.updated).max().unwrap_or_else(|| Utc::now().timestamp());
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        xml += &format!("<id>{}</id>\n<title>{}</title>\n", escape_xml(&self.id), escape_xml(&self.title));
+        xml += &format!("<updated>{}</updated>\n<generator>Telescope</generator>\n", rfc3339(updated).unwrap_or_default());
+        if let Some(url) = self.url(Format::Atom) {
+            xml += &format!("<link rel=\"self\" href=\"{}\"/>\n", escape_xml(&url));
+        }
+        for session in &self.sessions {
+            xml += "<entry>\n";
+            xml += &format!("<id>{}</id>\n<title>{}</title>\n", session.entry_id(), escape_xml(&session.entry_title()));
+            xml += &format!("<link href=\"{}\"/>\n", escape_xml(&session.url));
+            xml += &format!("<updated>{}</updated>\n", rfc3339(session.updated).unwrap_or_default());
+            if let Some(published) = session.start_time.and_then(rfc3339) {
+                xml += &format!("<published>{published}</published>\n");
+            }
+            xml += &format!("<author><name>{}</name>", escape_xml(&session.creator));
+            if let Some(profile_url) = &session.profile_url {
+                xml += &format!("<uri>{}</uri>", escape_xml(profile_url));
+            }
+            xml += "</author>\n";
+            xml += &format!("<content type=\"html\">{}</content>\n</entry>\n", escape_xml(&session.content_html()));
+        }
+        xml + "</feed>\n"
+    }
+
+    /// JSON Feed 1.1
+    pub fn json(&self) -> Value {
+        let items: Vec<_> = self.sessions.iter().map(|session| json!({
+            "id": session.entry_id(),
+            "url": session.url,
+            "title": session.entry_title(),
+            "content_html": session.content_html(),
+            "image": session.cover,
+            "date_published": session.start_time.and_then(rfc3339),
+            "date_modified": rfc3339(session.updated),
+            "authors": [{ "name": session.creator, "url": session.profile_url }]
+        })).collect();
+        json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "feed_url": self.url(Format::Json),
+            "items": items
+        })
+    }
+
+    fn response(&self, format: Format) -> Response {
+        match format {
+            Format::Atom => ([(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")], self.atom()).into_response(),
+            Format::Json => ([(header::CONTENT_TYPE, "application/feed+json")], Json(self.json())).into_response(),
+            Format::Ics => {
+                ([(header::CONTENT_TYPE, "text/calendar; charset=utf-8")], self.ics(Utc::now().timestamp())).into_response()
+            }
+        }
+    }
+}
+
+fn internal_error(e: impl Display) -> StatusCode {
+    log::error!("Failed to build feed: {e}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// Field of the feed token in the `settings:<chat_id>` hash, the `feeds` hash mapping tokens back to chats
+const TOKEN_FIELD: &str = "feed_token";
+
+/// Secret token of the feed of the chat, created on first use
+pub async fn token(db: &mut MultiplexedConnection, chat_id: impl Display) -> RedisResult<String> {
+    match db.hget(settings::key(&chat_id), TOKEN_FIELD).await? {
+        Some(token) => Ok(token),
+        None => reset_token(db, chat_id).await
+    }
+}
+
+/// Replace the feed token of the chat, so the previous feed URLs stop working
+pub async fn reset_token(db: &mut MultiplexedConnection, chat_id: impl Display) -> RedisResult<String> {
+    let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+    let old_token: Option<String> = db.hget(settings::key(&chat_id), TOKEN_FIELD).await?;
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .hset(settings::key(&chat_id), TOKEN_FIELD, &token)
+        .hset(schema::key("feeds"), &token, chat_id.to_string());
+    if let Some(old_token) = old_token {
+        pipe.hdel(schema::key("feeds"), old_token);
+    }
+    pipe.exec_async(db).await?;
+    Ok(token)
+}
+
+/// Public URL of the feed of the chat without its extension, if the HTTP server is public
+pub fn chat_feed_url(token: &str) -> Option<Url> {
+    public_url()?.join(&format!("feeds/{token}")).ok()
+}
+
+pub async fn chat_feed(State(state): State<AppState>, Path(file): Path<String>) -> Result<Response, StatusCode> {
+    let (token, format) = Format::split(&file).ok_or(StatusCode::NOT_FOUND)?;
+    let mut db = state.db;
+    let chat_id: Option<String> = db.hget(schema::key("feeds"), token).await.map_err(internal_error)?;
+    let chat_id = chat_id.ok_or(StatusCode::NOT_FOUND)?.parse().map(ChatId).map_err(internal_error)?;
+    let subs: Vec<Subscription> = db.smembers(Action::Subscribe.chat_key(chat_id)).await.map_err(internal_error)?;
+    let feed = Feed {
+        id: format!("urn:telescope:chat:{chat_id}"),
+        title: "Telescope".to_owned(),
+        url: chat_feed_url(token),
+        sessions: history::sessions(&mut db, &subs).await.map_err(internal_error)?
+    };
+    Ok(feed.response(format))
+}
+
+/// Split the feed file name of a subscription into the user ID and the format, rejecting anything but the numeric IDs
+/// of the platforms since the ID is matched against the subscriptions as a glob-style pattern
+fn user_id(file: &str) -> Option<(&str, Format)> {
+    Format::split(file).filter(|(user_id, _)| !user_id.is_empty() && user_id.bytes().all(|b| b.is_ascii_digit()))
+}
+
+pub async fn subscription_feed(
+    State(state): State<AppState>, Path((platform, file)): Path<(String, String)>
+) -> Result<Response, StatusCode> {
+    let (user_id, format) = user_id(&file).ok_or(StatusCode::NOT_FOUND)?;
+    let platform = Platform::iter().find(|p| p.short_name() == platform).ok_or(StatusCode::NOT_FOUND)?;
+    let mut db = state.db;
+    let sub = {
+        let pattern = format!("{platform}:{user_id}:*");
+        let mut iter: AsyncIter<(Subscription, String)> = db.hscan_match(schema::key("subs"), pattern).await
+            .map_err(internal_error)?;
+        iter.next_item().await.map(|(sub, _)| sub).ok_or(StatusCode::NOT_FOUND)?
+    };
+    let feed = Feed {
+        id: format!("urn:telescope:{}:{}", platform.short_name(), sub.user.id),
+        title: format!("{} - {platform}", sub.user.username),
+        url: public_url().and_then(|url| url.join(&format!("feeds/{}/{}", platform.short_name(), sub.user.id)).ok()),
+        sessions: history::sessions(&mut db, std::slice::from_ref(&sub)).await.map_err(internal_error)?
+    };
+    Ok(feed.response(format))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn feed() -> Feed {
+        Feed {
+            id: "urn:telescope:chat:1".to_owned(),
+            title: "Telescope".to_owned(),
+            url: Some("https://telescope.example.com/feeds/token".parse().unwrap()),
+            sessions: vec![Session {
+                platform: "bilibili".to_owned(),
+                user_id: "12345".to_owned(),
+                username: "Telescope".to_owned(),
+                creator: "Telescope".to_owned(),
+                live_id: "67890".to_owned(),
+                title: "<Test> & more".to_owned(),
+                url: "https://live.bilibili.com/67890".to_owned(),
+                profile_url: Some("https://space.bilibili.com/12345".to_owned()),
+                cover: Some("https://example.com/cover.jpg".to_owned()),
+                start_time: Some(1735732800),
+                end_time: Some(1735736400),
+                updated: 1735736400
+            }]
+        }
+    }
+
+    #[test]
+    fn atom() {
+        let atom = feed().atom();
+        assert!(atom.contains("<link rel=\"self\" href=\"https://telescope.example.com/feeds/token.atom\"/>"));
+        assert!(atom.contains("<id>urn:telescope:bilibili:67890</id>"));
+        assert!(atom.contains("<title>Telescope: &lt;Test&gt; &amp; more</title>"));
+        assert!(atom.contains("<published>2025-01-01T12:00:00+00:00</published>"));
+        assert!(atom.contains("Ended: 2025-01-01 13:00 UTC (1:00:00)"));
+    }
+
+    #[test]
+    fn json() {
+        let json = feed().json();
+        assert_eq!(json["feed_url"], "https://telescope.example.com/feeds/token.json");
+        assert_eq!(json["items"][0]["title"], "Telescope: <Test> & more");
+        assert_eq!(json["items"][0]["image"], "https://example.com/cover.jpg");
+        assert_eq!(json["items"][0]["date_published"], "2025-01-01T12:00:00+00:00");
+    }
+
+    #[test]
+    fn subscription_user_id() {
+        assert!(matches!(user_id("67890.atom"), Some(("67890", Format::Atom))));
+        for file in ["*.atom", "6789?.json", "[0-9]*.ics", "67890\\*.atom", ".atom"] {
+            assert!(user_id(file).is_none(), "{file}");
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, Json};
+use redis::aio::MultiplexedConnection;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use tokio::time;
+
+use super::AppState;
+use crate::{apis::TWITTER_API, platform::Platform, watcher::{health, lease}};
+
+#[derive(Serialize)]
+struct PlatformHealth {
+    platform: &'static str,
+    /// UNIX timestamp of the last completed check, or of the last wait for the lease
+    last_check: Option<i64>,
+    /// Whether the watcher of the platform is hung
+    stalled: bool,
+    /// Whether this instance polls the platform, which is polled by another instance otherwise
+    leader: bool
+}
+
+#[derive(Serialize)]
+pub struct Health {
+    redis: bool,
+    /// Whether every platform has completed a check recently
+    watcher: bool,
+    platforms: Vec<PlatformHealth>,
+    /// Twitter accounts which are neither rate limited nor disabled, if the Twitter API is initialized
+    twitter_accounts: Option<usize>
+}
+
+impl Health {
+    async fn check(db: &mut MultiplexedConnection) -> Self {
+        let cmd = redis::cmd("PING");
+        let ping = cmd.query_async::<String>(db);
+        Self {
+            redis: time::timeout(Duration::from_secs(2), ping).await.is_ok_and(|pong| pong.is_ok()),
+            watcher: !Platform::iter().any(|platform| health::stalled(&platform)),
+            platforms: Platform::iter().map(|platform| PlatformHealth {
+                platform: platform.short_name(),
+                last_check: health::last_check(&platform).map(|time| time.timestamp()),
+                stalled: health::stalled(&platform),
+                leader: lease::is_held(&platform)
+            }).collect(),
+            twitter_accounts: TWITTER_API.get().map(|api| api.accounts().active())
+        }
+    }
+
+    /// Ready to serve subscriptions: the database is reachable, every platform has been checked
+    /// and Twitter can be polled
+    fn ready(&self) -> bool {
+        self.redis
+            && self.watcher
+            && self.platforms.iter().all(|p| p.last_check.is_some())
+            && self.twitter_accounts.is_some_and(|active| active > 0)
+    }
+}
+
+fn status(ok: bool) -> StatusCode {
+    match ok {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// Liveness: fails only when the watcher is hung, which a restart of the bot may fix
+pub async fn healthz(State(mut state): State<AppState>) -> (StatusCode, Json<Health>) {
+    let health = Health::check(&mut state.db).await;
+    (status(health.watcher), Json(health))
+}
+
+pub async fn readyz(State(mut state): State<AppState>) -> (StatusCode, Json<Health>) {
+    let health = Health::check(&mut state.db).await;
+    (status(health.ready()), Json(health))
+}
@@ -0,0 +1,30 @@
+use std::fmt::Display;
+
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisResult};
+use strum_macros::{Display, EnumIter, EnumString};
+
+use crate::schema;
+
+/// Key of the hash holding the settings of the chat
+pub fn key(chat_id: impl Display) -> String {
+    schema::key(format!("settings:{chat_id}"))
+}
+
+/// Per-chat on/off settings, stored in the `settings:<chat_id>` hash and described by the `setting.<name>` messages
+#[derive(Clone, Copy, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Setting {
+    EditInPlace,
+    ChangeNotifications
+}
+
+impl Setting {
+    pub async fn get(&self, db: &mut MultiplexedConnection, chat_id: impl Display) -> RedisResult<bool> {
+        let value: Option<String> = db.hget(key(chat_id), self.to_string()).await?;
+        Ok(value.is_some_and(|v| v == "on"))
+    }
+
+    pub async fn set(&self, db: &mut MultiplexedConnection, chat_id: impl Display, enabled: bool) -> RedisResult<()> {
+        db.hset(key(chat_id), self.to_string(), if enabled { "on" } else { "off" }).await
+    }
+}
@@ -0,0 +1,63 @@
+use std::{future::Future, time::Duration};
+
+use lazy_static::lazy_static;
+use tokio::{signal, task, time};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+/// Longest time given to the work in flight to finish once the bot is asked to stop, below the 90 seconds systemd
+/// waits before killing a service
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    /// Cancelled when the bot is asked to stop
+    static ref TOKEN: CancellationToken = CancellationToken::new();
+    /// Background work the bot waits for before exiting, like the watchers and the deliveries to webhooks
+    static ref TASKS: TaskTracker = TaskTracker::new();
+}
+
+pub fn is_cancelled() -> bool {
+    TOKEN.is_cancelled()
+}
+
+/// Wait until the bot is asked to stop
+pub async fn cancelled() {
+    TOKEN.cancelled().await
+}
+
+/// Ask the watchers and the background work to stop
+pub fn cancel() {
+    TOKEN.cancel();
+}
+
+/// Run the future in the background, the bot waiting for it to finish before exiting
+pub fn spawn<F>(future: F) -> task::JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static
+{
+    TASKS.spawn(future)
+}
+
+/// Wait for Ctrl-C or SIGTERM, which systemd sends to stop the service
+pub async fn signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate()).expect("Failed to listen for SIGTERM").recv().await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = signal::ctrl_c() => (),
+        _ = terminate => ()
+    }
+}
+
+/// Wait for the background work to finish, at most for [`TIMEOUT`]
+pub async fn drain() {
+    cancel();
+    TASKS.close();
+    log::info!("Waiting for {} background tasks", TASKS.len());
+    if time::timeout(TIMEOUT, TASKS.wait()).await.is_err() {
+        log::warn!("{} background tasks didn't finish in time", TASKS.len());
+    }
+}
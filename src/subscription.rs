@@ -4,10 +4,10 @@ use lazy_regex::{lazy_regex, Lazy};
 use redis::{ErrorKind, FromRedisValue, RedisError, ToRedisArgs};
 use regex::Regex;
 use strum_macros::Display;
-use teloxide::utils::{command::ParseError, markdown::{bold, escape}};
+use teloxide::utils::markdown::{bold, escape};
 use url::Url;
 
-use crate::platform::{Platform, User};
+use crate::{apis::error::Error, i18n::Language, platform::{Platform, User}, schema};
 
 #[derive(Clone)]
 pub struct Subscription {
@@ -47,6 +47,50 @@ pub enum SubscriptionError {
     InvalidFormat
 }
 
+/// Reason a URL can't be turned into a subscription
+pub enum UrlError {
+    InvalidUrl(String),
+    UnsupportedPlatform(String),
+    /// The path isn't a username or room ID of the platform
+    InvalidUser(Platform),
+    /// The user couldn't be looked up
+    Lookup(Platform, Error)
+}
+
+impl Display for UrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrlError::InvalidUrl(e) => write!(f, "Invalid URL: {e}"),
+            UrlError::UnsupportedPlatform(host) => write!(f, "Unsupported platform: {host}"),
+            UrlError::InvalidUser(platform) => write!(f, "Invalid {platform} user"),
+            UrlError::Lookup(platform, e) => write!(f, "{platform}: {e}")
+        }
+    }
+}
+
+impl UrlError {
+    /// MarkdownV2 explanation of the error for the user
+    pub fn message(&self, lang: Language) -> String {
+        match self {
+            UrlError::InvalidUrl(e) => lang.markdown("url_error.invalid_url", &[("error", &escape(e))]),
+            UrlError::UnsupportedPlatform(host) => {
+                lang.markdown("url_error.unsupported_platform", &[("host", &escape(host))])
+            }
+            UrlError::InvalidUser(platform) => {
+                lang.markdown("url_error.invalid_user", &[("platform", &escape(&platform.to_string()))])
+            }
+            UrlError::Lookup(platform, e) => {
+                let key = match e {
+                    Error::NotFound(_) => "url_error.not_found",
+                    e if e.is_transient() => "url_error.unavailable",
+                    _ => "url_error.api"
+                };
+                lang.markdown(key, &[("platform", &escape(&platform.to_string())), ("error", &escape(&e.to_string()))])
+            }
+        }
+    }
+}
+
 impl FromStr for Subscription {
     type Err = SubscriptionError;
 
@@ -63,22 +107,32 @@ impl FromStr for Subscription {
 impl Subscription {
     const URL_REGEX: Lazy<Regex> = lazy_regex!(r"^https?://.+$");
 
-    async fn from_host_and_path(host: &str, path: &str) -> Result<Self, ParseError> {
-        let platform: Platform = host.parse().or(Err(ParseError::Custom(format!("Unsupported platform: {host}").into())))?;
-        let user = platform.parse_user(path).await.ok_or(ParseError::IncorrectFormat("Invalid username".into()))?;
+    async fn from_host_and_path(host: &str, path: &str) -> Result<Self, UrlError> {
+        let platform: Platform = host.parse().or(Err(UrlError::UnsupportedPlatform(host.to_owned())))?;
+        let user = platform.parse_user(path).await?;
         Ok(Self { platform, user })
     }
 
-    pub async fn from_url(mut input: String) -> Result<Self, ParseError> {
+    pub async fn from_url(mut input: String) -> Result<Self, UrlError> {
         if !Self::URL_REGEX.is_match(&input) {
             input = format!("https://{input}");
         }
-        let url = input.parse::<Url>().map_err(|err| ParseError::IncorrectFormat(err.into()))?;
-        let host = url.host_str().ok_or(ParseError::IncorrectFormat("Hostname not found".into()))?;
+        let url = input.parse::<Url>().map_err(|err| UrlError::InvalidUrl(err.to_string()))?;
+        let host = url.host_str().ok_or(UrlError::InvalidUrl("Hostname not found".to_owned()))?;
         Self::from_host_and_path(host, url.path()).await
     }
 
     pub fn to_db_string(&self) -> String {
         format!("{}:{}:{}", self.platform, self.user.id, self.user.username)
     }
+
+    /// Key of the hash of the chats subscribed to the lives of the user, with the message notifying the current live
+    pub fn key(&self) -> String {
+        schema::key(format!("sub:{}", self.to_db_string()))
+    }
+
+    /// Key of the set of the chats subscribed to the appearances of the user
+    pub fn appearances_key(&self) -> String {
+        schema::key(format!("appearances:{}", self.to_db_string()))
+    }
 }
@@ -0,0 +1,346 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisResult};
+use strum_macros::{Display, EnumIter, EnumString};
+use teloxide::utils::markdown::{escape, escape_code, escape_link_url};
+
+use crate::{
+    apis::{LiveState, Metadata},
+    i18n::{self, Language},
+    platform::Platform,
+    schema
+};
+
+/// Notification events which can be customized with templates
+#[derive(Clone, Copy, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "lowercase")]
+pub enum Event {
+    Start,
+    End
+}
+
+/// Values of a notification, described by the `placeholder.<name>` messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Placeholder {
+    Platform,
+    Creator,
+    Handle,
+    ProfileUrl,
+    Title,
+    Url,
+    StartTime,
+    Duration,
+    MasterUrl,
+    Audience,
+    ReplayUrl
+}
+
+pub type Placeholders = HashMap<Placeholder, String>;
+
+#[derive(Debug)]
+pub enum TemplateError {
+    UnknownPlaceholder(String),
+    UnclosedTag,
+    UnescapedBrace,
+    UnclosedSection(Placeholder),
+    UnexpectedClose(String)
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder(name) => write!(f, "Unknown placeholder: {name}"),
+            TemplateError::UnclosedTag => f.write_str("Missing } after placeholder"),
+            TemplateError::UnescapedBrace => f.write_str("Unescaped } outside of placeholder"),
+            TemplateError::UnclosedSection(name) => write!(f, "Section {name} is not closed with {{/{name}}}"),
+            TemplateError::UnexpectedClose(name) => write!(f, "Unexpected {{/{name}}}")
+        }
+    }
+}
+
+enum Token {
+    Text(String),
+    Value(Placeholder),
+    /// Rendered only if the placeholder has a value, or only if it hasn't when inverted
+    Section { placeholder: Placeholder, inverted: bool, tokens: Vec<Token> }
+}
+
+/// MarkdownV2 entity the renderer is in, which decides how values are escaped
+#[derive(Clone, Copy, PartialEq)]
+enum Context {
+    Text,
+    Code,
+    Pre,
+    LinkUrl
+}
+
+struct Renderer {
+    output: String,
+    context: Context
+}
+
+impl Renderer {
+    fn push_text(&mut self, text: &str) {
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let next = |n: usize| chars.get(i + n).copied();
+            match (self.context, chars[i]) {
+                (_, '\\') => i += 1,
+                (Context::Text | Context::Pre, '`') if next(1) == Some('`') && next(2) == Some('`') => {
+                    self.context = if self.context == Context::Pre { Context::Text } else { Context::Pre };
+                    i += 2;
+                }
+                (Context::Text, '`') => self.context = Context::Code,
+                (Context::Code, '`') => self.context = Context::Text,
+                (Context::Text, ']') if next(1) == Some('(') => {
+                    self.context = Context::LinkUrl;
+                    i += 1;
+                }
+                (Context::LinkUrl, ')') => self.context = Context::Text,
+                _ => ()
+            }
+            i += 1;
+        }
+        self.output.push_str(text);
+    }
+
+    fn push_value(&mut self, value: &str) {
+        self.output += &match self.context {
+            Context::Text => escape(value),
+            Context::Code | Context::Pre => escape_code(value),
+            Context::LinkUrl => escape_link_url(value)
+        };
+    }
+
+    fn render(&mut self, tokens: &[Token], values: &Placeholders) {
+        for token in tokens {
+            match token {
+                Token::Text(text) => self.push_text(text),
+                Token::Value(placeholder) => self.push_value(values.get(placeholder).map(String::as_str).unwrap_or_default()),
+                Token::Section { placeholder, inverted, tokens } => {
+                    if values.get(placeholder).is_some_and(|v| !v.is_empty()) != *inverted {
+                        self.render(tokens, values);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A MarkdownV2 message with `{placeholder}` values, `{#placeholder}...{/placeholder}` sections
+/// and `{^placeholder}...{/placeholder}` inverted sections. Values are escaped when rendered.
+pub struct Template(Vec<Token>);
+
+/// Placeholder of a section being parsed and whether it is inverted, with its tokens so far.
+/// The root of the template has no placeholder.
+type OpenSection = (Option<(Placeholder, bool)>, Vec<Token>);
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn placeholder(name: &str) -> Result<Placeholder, TemplateError> {
+            name.parse().or(Err(TemplateError::UnknownPlaceholder(name.to_owned())))
+        }
+        let mut stack: Vec<OpenSection> = vec![(None, vec![])];
+        let mut text = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    text.push(c);
+                    text.extend(chars.next());
+                }
+                '{' => {
+                    let mut tag = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => tag.push(c),
+                            None => return Err(TemplateError::UnclosedTag)
+                        }
+                    }
+                    let tokens = &mut stack.last_mut().expect("template root popped").1;
+                    if !text.is_empty() {
+                        tokens.push(Token::Text(std::mem::take(&mut text)));
+                    }
+                    let tag = tag.trim();
+                    if let Some(name) = tag.strip_prefix('#') {
+                        stack.push((Some((placeholder(name)?, false)), vec![]));
+                    } else if let Some(name) = tag.strip_prefix('^') {
+                        stack.push((Some((placeholder(name)?, true)), vec![]));
+                    } else if let Some(name) = tag.strip_prefix('/') {
+                        let Some((Some((placeholder, inverted)), tokens)) = stack.pop_if(
+                            |(section, _)| section.is_some_and(|(p, _)| p.to_string() == name)
+                        ) else {
+                            return Err(TemplateError::UnexpectedClose(name.to_owned()));
+                        };
+                        stack.last_mut().expect("template root popped").1.push(Token::Section { placeholder, inverted, tokens });
+                    } else {
+                        tokens.push(Token::Value(placeholder(tag)?));
+                    }
+                }
+                '}' => return Err(TemplateError::UnescapedBrace),
+                c => text.push(c)
+            }
+        }
+        let (section, mut tokens) = stack.pop().expect("template root popped");
+        if let Some((placeholder, _)) = section {
+            return Err(TemplateError::UnclosedSection(placeholder));
+        }
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+        Ok(Template(tokens))
+    }
+}
+
+impl Template {
+    /// Default template of the language, stored in the catalog as `template.<platform>.<event>`
+    pub fn default_source(language: Language, platform: &Platform, event: &Event) -> &'static str {
+        let key = format!("template.{}.{event}", platform.short_name());
+        language.find(&key).or_else(|| Language::En.find(&key)).expect("Missing default template")
+    }
+
+    pub fn render(&self, values: &Placeholders) -> String {
+        let mut renderer = Renderer { output: String::new(), context: Context::Text };
+        renderer.render(&self.0, values);
+        renderer.output
+    }
+}
+
+/// Values used to preview a template before it is saved
+pub fn sample(platform: &Platform, event: &Event) -> Placeholders {
+    let mut values = Placeholders::from([
+        (Placeholder::Platform, platform.to_string()),
+        (Placeholder::Creator, "Telescope".to_owned()),
+        (Placeholder::Title, "Template preview (with [special] characters!)".to_owned()),
+        (Placeholder::StartTime, "2025-01-01 12:00 UTC".to_owned()),
+        (Placeholder::Audience, "1234".to_owned())
+    ]);
+    match platform {
+        Platform::TwitterSpace => values.extend([
+            (Placeholder::Handle, "@telescope".to_owned()),
+            (Placeholder::ProfileUrl, "https://twitter.com/telescope".to_owned()),
+            (Placeholder::Url, "https://twitter.com/i/spaces/1OdKrjzyLDyKX".to_owned())
+        ]),
+        Platform::BilibiliLive => values.extend([
+            (Placeholder::Handle, "12345".to_owned()),
+            (Placeholder::ProfileUrl, "https://space.bilibili.com/12345".to_owned()),
+            (Placeholder::Url, "https://live.bilibili.com/67890".to_owned())
+        ])
+    }
+    match event {
+        Event::Start if matches!(platform, Platform::TwitterSpace) => {
+            values.insert(Placeholder::MasterUrl, "https://prod-fastly-ap-northeast-1.video.pscp.tv/master_playlist.m3u8".to_owned());
+        }
+        Event::Start => (),
+        Event::End => {
+            values.insert(Placeholder::Duration, "1:23:45".to_owned());
+            if matches!(platform, Platform::TwitterSpace) {
+                values.insert(Placeholder::ReplayUrl, values[&Placeholder::Url].clone());
+            }
+        }
+    }
+    values
+}
+
+fn key(chat_id: impl Display) -> String {
+    schema::key(format!("templates:{chat_id}"))
+}
+
+fn field(platform: &Platform, event: &Event) -> String {
+    format!("{}:{event}", platform.short_name())
+}
+
+/// Get the template source the chat has saved for the event, if any
+pub async fn get(
+    db: &mut MultiplexedConnection, chat_id: impl Display, platform: &Platform, event: &Event
+) -> RedisResult<Option<String>> {
+    db.hget(key(chat_id), field(platform, event)).await
+}
+
+pub async fn save(
+    db: &mut MultiplexedConnection, chat_id: impl Display, platform: &Platform, event: &Event, source: &str
+) -> RedisResult<()> {
+    db.hset(key(chat_id), field(platform, event), source).await
+}
+
+pub async fn reset(db: &mut MultiplexedConnection, chat_id: impl Display, platform: &Platform, event: &Event) -> RedisResult<()> {
+    db.hdel(key(chat_id), field(platform, event)).await
+}
+
+/// Render the notification of the live with the template of the chat, or the platform default
+pub async fn render(db: &mut MultiplexedConnection, chat_id: impl Display, live: &impl Metadata) -> String {
+    let language = i18n::get(db, &chat_id).await;
+    let event = match live.get_state() {
+        LiveState::Running => Event::Start,
+        LiveState::Ended | LiveState::TimedOut => Event::End,
+        LiveState::Unknown(state) => return language.markdown("live.unknown_state", &[("state", &escape(state))])
+    };
+    let platform = live.to_sub().platform;
+    let template = match get(db, &chat_id, &platform, &event).await {
+        Ok(Some(source)) => source.parse::<Template>().inspect_err(|e| log::error!("Invalid template of {chat_id}: {e}")).ok(),
+        Ok(None) => None,
+        Err(e) => {
+            log::error!("Failed to get template of {chat_id}: {e}");
+            None
+        }
+    };
+    let template = template.unwrap_or_else(|| {
+        Template::default_source(language, &platform, &event).parse().expect("Invalid default template")
+    });
+    template.render(&live.placeholders())
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    #[test]
+    fn default_templates() {
+        for language in Language::iter() {
+            for platform in Platform::iter() {
+                for event in Event::iter() {
+                    assert!(Template::default_source(language, &platform, &event).parse::<Template>().is_ok());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn escaping() {
+        let template: Template = "*{title}* [{title}]({url})\n```\n{title} {url}\n```".parse().unwrap();
+        let values = Placeholders::from([
+            (Placeholder::Title, "a_b (c)`".to_owned()),
+            (Placeholder::Url, "https://example.com/a_(b)".to_owned())
+        ]);
+        assert_eq!(
+            template.render(&values),
+            "*a\\_b \\(c\\)\\`* [a\\_b \\(c\\)\\`](https://example.com/a_(b\\))\n```\na_b (c)\\` https://example.com/a_(b)\n```"
+        );
+    }
+
+    #[test]
+    fn sections() {
+        let template: Template = "{title}{#duration} {duration}{/duration}{^duration} live{/duration}".parse().unwrap();
+        let mut values = Placeholders::from([(Placeholder::Title, "t".to_owned())]);
+        assert_eq!(template.render(&values), "t live");
+        values.insert(Placeholder::Duration, "1:00:00".to_owned());
+        assert_eq!(template.render(&values), "t 1:00:00");
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert!(matches!("{creator".parse::<Template>(), Err(TemplateError::UnclosedTag)));
+        assert!(matches!("{name}".parse::<Template>(), Err(TemplateError::UnknownPlaceholder(_))));
+        assert!(matches!("{#title}".parse::<Template>(), Err(TemplateError::UnclosedSection(Placeholder::Title))));
+        assert!(matches!("{#title}{/url}".parse::<Template>(), Err(TemplateError::UnexpectedClose(_))));
+        assert!(matches!("a}".parse::<Template>(), Err(TemplateError::UnescapedBrace)));
+        assert!("\\{not a placeholder\\}".parse::<Template>().is_ok());
+    }
+}
@@ -1,86 +1,532 @@
-use std::{fmt::Display, sync::Arc, time::Duration};
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc
+    },
+    time::Duration
+};
 
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
 use redis::{aio::MultiplexedConnection, AsyncCommands, AsyncIter};
+use strum::IntoEnumIterator;
 use teloxide::{
-    payloads::{SendDocumentSetters, SendPhotoSetters},
+    payloads::{EditMessageCaptionSetters, SendDocumentSetters, SendPhotoSetters},
     prelude::Requester,
     sugar::request::{RequestLinkPreviewExt, RequestReplyExt},
-    types::MessageId
+    types::{InputMedia, InputMediaPhoto, MessageId, ParseMode},
+    utils::markdown::{escape, link, strike}
 };
 use tokio::{task, time};
+use url::Url;
 
+use self::{
+    poller::Poller,
+    sinks::{EventKind, LiveEvent}
+};
 use crate::{
-    apis::{get_bilibili_api, get_twitter_api, LiveState, Metadata, API},
+    apis::{
+        get_bilibili_api,
+        get_twitter_api,
+        twitter::{accounts::AccountPool, TwitterAPI},
+        LiveState,
+        Metadata,
+        API,
+        CONCURRENCY
+    },
+    config,
+    handlers::owner,
+    history,
+    i18n::{self, Language},
+    log_utils::LogResult,
+    metrics::{self, CountError, LIVES_DETECTED, SUBSCRIPTIONS, WATCHER_CYCLE_DURATION},
     platform::Platform,
+    schema,
+    settings::Setting,
+    shutdown,
     subscription::Subscription,
+    templates,
     Bot
 };
 
-pub async fn check<T: Metadata + Display>(api: &Arc<impl API<T>>, db: &mut MultiplexedConnection, bot: &Bot, platform: Platform) {
+pub mod health;
+pub mod lease;
+pub mod poller;
+pub mod sinks;
+
+fn live_key(platform: &Platform, live_id: impl Display) -> String {
+    schema::key(format!("live:{platform}:{live_id}"))
+}
+
+/// Significant changes of a running live, compared to what subscribers were last told about
+struct LiveChange {
+    old_title: Option<String>,
+    cover_changed: bool
+}
+
+impl LiveChange {
+    /// Minimum time between two change notifications of the same live, so a streamer
+    /// trying out several titles in a row only triggers one notification
+    const COOLDOWN: chrono::Duration = chrono::Duration::minutes(5);
+
+    fn normalize(title: &str) -> String {
+        title.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+    }
+
+    fn message(&self, language: Language, sub: &Subscription, live: &impl Metadata) -> String {
+        let mut text = language.markdown("change.updated", &[("sub", sub)]);
+        if let Some(old_title) = &self.old_title {
+            text += "\n";
+            text += &language.markdown("change.title", &[
+                ("old", &strike(escape(old_title).as_str())),
+                ("new", &link(live.get_url().as_str(), escape(live.get_title()).as_str()))
+            ]);
+        }
+        if self.cover_changed {
+            text += "\n";
+            text += &language.markdown("change.cover", &[]);
+        }
+        text
+    }
+}
+
+/// Whether this instance still holds the lease of the platform before it sends a notification, since another
+/// instance takes over the platform if a check outlasts the lease
+fn still_leader(platform: &Platform) -> bool {
+    let held = lease::is_held(platform);
+    if !held {
+        log::warn!("Lost the {platform} lease, stopping the check");
+    }
+    held
+}
+
+/// Update or notify the subscribers of a running live once its title or cover has changed
+async fn check_changes<T: Metadata>(live: &T, sub: &Subscription, key: &str, db: &mut MultiplexedConnection, bot: &Bot) {
+    let Some((title, cover, changed_at)): Option<(Option<String>, Option<String>, Option<i64>)> = redis::pipe()
+        .hget(key, "title").hget(key, "cover").hget(key, "changed_at")
+        .query_async(db).await.count_err().log_ok("Failed to get live") else {
+        return;
+    };
+    let new_cover = live.get_cover().map(Url::to_string);
+    let change = LiveChange {
+        old_title: title.filter(|title| LiveChange::normalize(title) != LiveChange::normalize(live.get_title())),
+        cover_changed: cover.is_some() && cover != new_cover
+    };
+    if change.old_title.is_none() && !change.cover_changed {
+        if cover.is_none() {
+            // the live started before covers were tracked
+            let _: Option<()> = db.hset(key, "cover", new_cover).await.count_err().log_ok("Failed to save live cover");
+        }
+        return;
+    }
+    if changed_at.and_then(|t| DateTime::from_timestamp(t, 0)).is_some_and(|t| t + LiveChange::COOLDOWN > Utc::now()) {
+        return;
+    }
+    if !still_leader(&sub.platform) {
+        return;
+    }
+    let _: Option<()> = redis::pipe()
+        .hset(key, "title", live.get_title())
+        .hset(key, "cover", new_cover)
+        .hset(key, "changed_at", Utc::now().timestamp())
+        .exec_async(db).await.count_err().log_ok("Failed to save live");
+    let mut event = LiveEvent::new(EventKind::Change, live);
+    event.old_title = change.old_title.clone();
+    sinks::notify(db, sub, event).await;
+    let mut db_clone = db.clone();
+    let Ok(mut iter): Result<AsyncIter<(String, i32)>, _> = db_clone.hscan(sub.key()).await.count_err() else {
+        return;
+    };
+    while let Some((chat_id, msg_id)) = iter.next_item().await {
+        if msg_id == 0 {
+            continue;
+        }
+        if !still_leader(&sub.platform) {
+            return;
+        }
+        if Setting::EditInPlace.get(db, &chat_id).await.unwrap_or(false) {
+            let msg = templates::render(db, &chat_id, live).await;
+            log::info!("Editing message: {msg}");
+            if change.cover_changed {
+                let media = InputMediaPhoto::new(live.get_attachment()).caption(msg).parse_mode(ParseMode::MarkdownV2);
+                bot.edit_message_media(chat_id.clone(), MessageId(msg_id), InputMedia::Photo(media)).await
+                    .log_ok("Failed to edit notification");
+            } else {
+                bot.edit_message_caption(chat_id.clone(), MessageId(msg_id)).caption(msg).await
+                    .log_ok("Failed to edit notification");
+            }
+        }
+        if Setting::ChangeNotifications.get(db, &chat_id).await.unwrap_or(false) {
+            let msg = change.message(i18n::get(db, &chat_id).await, sub, live);
+            log::info!("Sending message: {msg}");
+            let sent = if change.cover_changed {
+                bot.send_photo(chat_id, live.get_attachment()).caption(msg).reply_to(MessageId(msg_id)).await
+            } else {
+                bot.send_message(chat_id, msg).disable_link_preview(true).reply_to(MessageId(msg_id)).await
+            }.log_ok("Failed to send change notification");
+            metrics::notification("telegram", sent.is_some());
+        }
+    }
+}
+
+pub async fn check<T: Metadata>(
+    api: &Arc<impl API<T>>, db: &mut MultiplexedConnection, bot: &Bot, platform: Platform, poller: &mut Poller
+) {
+    if poller.suspended() {
+        return;
+    }
+    let _timer = WATCHER_CYCLE_DURATION.with_label_values(&[platform.short_name()]).start_timer();
     let mut subs: Vec<Subscription> = vec![];
+    let mut count = 0;
     let mut db_clone = db.clone();
-    let Ok(mut iter): Result<AsyncIter<(Subscription, String)>, _> = db_clone.hscan_match("subs", format!("{platform}:*")).await else {
+    let Ok(mut iter): Result<AsyncIter<(Subscription, String)>, _> = db_clone.hscan_match(schema::key("subs"), format!("{platform}:*")).await.count_err() else {
         return;
     };
+    let mut lives: Vec<(Subscription, String)> = vec![];
     while let Some((sub, live_id)) = iter.next_item().await {
+        count += 1;
         if live_id.is_empty() {
-            subs.push(sub);
+            if poller.due(&sub) {
+                subs.push(sub);
+            }
         } else {
-            if let Some(live) = api.live_status(&live_id, None).await {
-                let mut db_clone = db.clone();
-                let mut iter: AsyncIter<(String, i32)> = db_clone.hscan(&sub).await.unwrap();
-                match live.get_state() {
-                    LiveState::Running => (),
-                    LiveState::Ended | LiveState::TimedOut => {
-                        while let Some((chat_id, msg_id)) = iter.next_item().await {
-                            let msg = live.to_string();
-                            log::info!("Sending message: {msg}");
-                            bot.send_message(chat_id.clone(), msg)
-                                .disable_link_preview(true).reply_to(MessageId(msg_id)).await.unwrap();
-                            redis::pipe().atomic()
-                                .hset("subs", &sub, "")
-                                .hset(&sub, chat_id, 0)
-                                .exec_async(db).await.unwrap();
-                        }
+            lives.push((sub, live_id));
+        }
+    }
+    SUBSCRIPTIONS.with_label_values(&[platform.short_name()]).set(count);
+    let mut statuses = stream::iter(lives)
+        .map(|(sub, live_id)| async move {
+            let live = api.live_status(&live_id, None).await;
+            (sub, live_id, live)
+        })
+        .buffer_unordered(CONCURRENCY);
+    while let Some((sub, live_id, live)) = statuses.next().await {
+        let mut live = match live {
+            Ok(live) => live,
+            Err(e) => {
+                log::error!("Failed to get {platform} {live_id}: {e}");
+                if e.is_platform_wide() {
+                    poller.platform_failed(&e);
+                }
+                if poller.report(&e) {
+                    owner::notify(bot, &format!("Failed to get {platform} {live_id}: {e}")).await;
+                }
+                continue;
+            }
+        };
+        let mut db_clone = db.clone();
+        let Some(mut iter): Option<AsyncIter<(String, i32)>> = db_clone.hscan(sub.key()).await.count_err()
+            .log_ok("Failed to get subscribers") else {
+            continue;
+        };
+        match live.get_state() {
+            LiveState::Running => check_changes(&live, &sub, &live_key(&platform, &live_id), db, bot).await,
+            LiveState::Ended | LiveState::TimedOut => {
+                if live.get_start_time().is_none() {
+                    let start_time: Option<Option<i64>> = db.hget(live_key(&platform, &live_id), "start_time").await
+                        .count_err().log_ok("Failed to get live start time");
+                    if let Some(start_time) = start_time.flatten().and_then(|t| DateTime::from_timestamp(t, 0)) {
+                        live.set_start_time(start_time);
                     }
-                    LiveState::Unknown(_) => {
-                        while let Some((chat_id, msg_id)) = iter.next_item().await {
-                            let msg = live.to_string();
-                            log::info!("Sending message: {msg}");
-                            bot.send_message(chat_id, msg).reply_to(MessageId(msg_id)).await.unwrap();
-                        }
+                }
+                while let Some((chat_id, msg_id)) = iter.next_item().await {
+                    if !still_leader(&platform) {
+                        return;
+                    }
+                    let msg = templates::render(db, &chat_id, &live).await;
+                    let edit_in_place = msg_id != 0 && Setting::EditInPlace.get(db, &chat_id).await.unwrap_or(false);
+                    // fall back to a reply if the start notification can't be edited, e.g. it was deleted
+                    let edited = edit_in_place && {
+                        log::info!("Editing message: {msg}");
+                        bot.edit_message_caption(chat_id.clone(), MessageId(msg_id)).caption(msg.clone()).await
+                            .log_ok("Failed to edit notification").is_some()
+                    };
+                    if !edited {
+                        log::info!("Sending message: {msg}");
+                        let sent = bot.send_message(chat_id.clone(), msg)
+                            .disable_link_preview(true).reply_to(MessageId(msg_id)).await
+                            .log_ok("Failed to send end notification");
+                        metrics::notification("telegram", sent.is_some());
                     }
+                    let _: Option<()> = db.hset(sub.key(), chat_id, 0).await.count_err()
+                        .log_ok("Failed to reset notification");
+                }
+                let _: Option<()> = db.hset(schema::key("subs"), &sub, "").await.count_err()
+                    .log_ok("Failed to reset live");
+                if !still_leader(&platform) {
+                    return;
+                }
+                sinks::notify(db, &sub, LiveEvent::new(EventKind::End, &live)).await;
+                let _: Option<()> = db.del(live_key(&platform, &live_id)).await.count_err().log_ok("Failed to delete live");
+            }
+            LiveState::Unknown(_) => {
+                while let Some((chat_id, msg_id)) = iter.next_item().await {
+                    if !still_leader(&platform) {
+                        return;
+                    }
+                    let msg = templates::render(db, &chat_id, &live).await;
+                    log::info!("Sending message: {msg}");
+                    bot.send_message(chat_id, msg).reply_to(MessageId(msg_id)).await
+                        .log_ok("Failed to send notification");
                 }
             }
         }
     }
-    for live in api.user_live_status(subs).await {
-        let sub = live.to_sub();
-        let subscribers: Vec<String> = db.hkeys(&sub).await.unwrap();
+    if poller.suspended() {
+        return;
+    }
+    let (lives, errors) = api.user_live_status(subs.clone()).await;
+    let failed: HashSet<_> = errors.iter().map(|(sub, _)| sub.to_db_string()).collect();
+    for (sub, e) in &errors {
+        log::error!("Failed to check {}: {e}", sub.to_db_string());
+        poller.failed(sub);
+        if poller.report(e) {
+            owner::notify(bot, &format!("Failed to check {}: {e}", sub.to_db_string())).await;
+        }
+    }
+    match errors.iter().find(|(_, e)| e.is_platform_wide()) {
+        Some((_, e)) => poller.platform_failed(e),
+        None if !subs.is_empty() => poller.platform_succeeded(),
+        None => ()
+    }
+    for sub in subs.iter().filter(|sub| !failed.contains(&sub.to_db_string())) {
+        let usually_live = history::usually_live(db, sub, Utc::now()).await.count_err()
+            .log_ok("Failed to get history").unwrap_or(true);
+        poller.polled(sub, usually_live);
+    }
+    for live in lives {
+        // the Spaces the polled users only appear in are left to the subscribers of their creator
+        let creator = live.to_sub();
+        let Some(sub) = subs.iter().find(|sub| sub.user.id == creator.user.id).cloned() else {
+            continue;
+        };
+        LIVES_DETECTED.with_label_values(&[platform.short_name()]).inc();
+        let key = live_key(&platform, live.get_id());
+        let mut pipe = redis::pipe();
+        pipe.hset(&key, "title", live.get_title()).hset(&key, "cover", live.get_cover().map(Url::to_string));
+        if let Some(start_time) = live.get_start_time() {
+            pipe.hset(&key, "start_time", start_time.timestamp());
+        }
+        let _: Option<()> = pipe.exec_async(db).await.count_err().log_ok("Failed to save live");
+        let Some(subscribers): Option<Vec<String>> = db.hkeys(sub.key()).await.count_err()
+            .log_ok("Failed to get subscribers") else {
+            continue;
+        };
         for chat_id in subscribers {
-            let msg_text = live.to_string();
+            if !still_leader(&platform) {
+                return;
+            }
+            let msg_text = templates::render(db, &chat_id, &live).await;
             log::info!("Sending message: {msg_text}");
             let msg = match platform {
                 Platform::TwitterSpace => bot.send_document(chat_id.clone(), live.get_attachment())
-                    .caption(msg_text).await.unwrap(),
+                    .caption(msg_text).await,
                 Platform::BilibiliLive => bot.send_photo(chat_id.clone(), live.get_attachment())
-                    .caption(msg_text).await.unwrap()
+                    .caption(msg_text).await
+            };
+            let msg = msg.log_ok("Failed to send notification");
+            metrics::notification("telegram", msg.is_some());
+            let Some(msg) = msg else {
+                continue;
             };
-            redis::pipe().atomic()
-                .hset("subs", &sub, live.get_id())
-                .hset(&sub, chat_id, msg.id.0)
-                .exec_async(db).await.unwrap();
+            let _: Option<()> = db.hset(sub.key(), chat_id, msg.id.0).await.count_err()
+                .log_ok("Failed to save notification");
+        }
+        // set after the notifications, so the live is notified again if the bot stops before
+        let saved = redis::pipe().hset(schema::key("subs"), &sub, live.get_id()).exec_async(db).await.count_err()
+            .log_ok("Failed to save live");
+        if saved.is_none() {
+            continue;
+        }
+        if !still_leader(&platform) {
+            return;
+        }
+        sinks::notify(db, &sub, LiveEvent::new(EventKind::Start, &live)).await;
+    }
+}
+
+/// Notify chats subscribed to the appearances of users who joined someone else's Space as co-host or speaker
+pub async fn check_appearances(api: &Arc<TwitterAPI>, db: &mut MultiplexedConnection, bot: &Bot) {
+    let mut appearances: Vec<(Subscription, String)> = vec![];
+    let mut db_clone = db.clone();
+    let Ok(mut iter): Result<AsyncIter<(Subscription, String)>, _> = db_clone.hscan(schema::key("appearances")).await.count_err() else {
+        return;
+    };
+    while let Some(item) = iter.next_item().await {
+        appearances.push(item);
+    }
+    if appearances.is_empty() {
+        return;
+    }
+    let (spaces, errors) = api.user_live_status(appearances.iter().map(|(sub, _)| sub.clone()).collect()).await;
+    let failed: HashSet<_> = errors.iter().map(|(sub, _)| sub.to_db_string()).collect();
+    // the appearances of the users who couldn't be checked are kept, so they aren't notified again
+    for (sub, live_id) in appearances.into_iter().filter(|(sub, _)| !failed.contains(&sub.to_db_string())) {
+        match spaces.iter().find_map(|space| space.appearance(&sub.user.id)) {
+            Some(appearance) if appearance.space.id != live_id => {
+                let Some(chats): Option<Vec<String>> = db.smembers(sub.appearances_key()).await
+                    .count_err().log_ok("Failed to get appearance subscribers") else {
+                    continue;
+                };
+                for chat_id in chats {
+                    if !still_leader(&Platform::TwitterSpace) {
+                        return;
+                    }
+                    let msg = appearance.message(i18n::get(db, &chat_id).await);
+                    log::info!("Sending message: {msg}");
+                    let sent = bot.send_message(chat_id, msg).await.log_ok("Failed to send appearance notification");
+                    metrics::notification("telegram", sent.is_some());
+                }
+                let _: Option<()> = db.hset(schema::key("appearances"), &sub, &appearance.space.id).await.count_err().log_ok("Failed to save appearance");
+            }
+            // not in any Space anymore, the next appearance will be notified again
+            None if !live_id.is_empty() => {
+                let _: Option<()> = db.hset(schema::key("appearances"), &sub, "").await.count_err().log_ok("Failed to reset appearance");
+            }
+            _ => ()
+        }
+    }
+}
+
+async fn report_health(bot: &Bot, accounts: &AccountPool) {
+    let alerts = accounts.take_alerts();
+    if alerts.is_empty() {
+        return;
+    }
+    owner::notify(bot, &format!("{}\n\n{accounts}", alerts.join("\n"))).await;
+}
+
+/// Polling schedule of a platform, configured in seconds by `<PLATFORM>_POLL_INTERVAL` and `<PLATFORM>_POLL_JITTER`,
+/// e.g. `TWITTER_POLL_INTERVAL`
+struct Schedule {
+    interval: Duration,
+    /// Maximum random delay added to the interval, so the requests don't follow a fixed pattern
+    jitter: Duration
+}
+
+impl Schedule {
+    fn from_env(platform: &Platform) -> Self {
+        let var = |name: &str, default: u64| {
+            let key = format!("{}_POLL_{name}", platform.short_name().to_uppercase());
+            config::var(&key).ok()
+                .and_then(|value| value.parse().inspect_err(|e| log::error!("Invalid {key}: {e}")).ok())
+                .unwrap_or(default)
+        };
+        Self { interval: Duration::from_secs(var("INTERVAL", 30)), jitter: Duration::from_secs(var("JITTER", 5)) }
+    }
+
+    fn delay(&self) -> Duration {
+        self.interval + self.jitter.mul_f64(rand::random())
+    }
+
+    /// Longest expected time between two checks
+    fn period(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.interval + self.jitter).unwrap_or(health::STALL_TIMEOUT)
+    }
+
+    fn poll_interval(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.interval).unwrap_or(chrono::Duration::seconds(30))
+    }
+}
+
+/// Incremented by [`reload`], so the watchers read their schedule again
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Apply the polling configuration of the environment again, from the next check of each platform
+pub fn reload() {
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+async fn check_platform(platform: &Platform, db: &mut MultiplexedConnection, bot: &Bot, poller: &mut Poller) {
+    match platform {
+        Platform::TwitterSpace => {
+            let twitter_api = get_twitter_api().await;
+            check(twitter_api, db, bot, Platform::TwitterSpace, poller).await;
+            if !poller.suspended() {
+                check_appearances(twitter_api, db, bot).await;
+            }
+            report_health(bot, &twitter_api.accounts()).await;
         }
+        Platform::BilibiliLive => check(get_bilibili_api().await, db, bot, Platform::BilibiliLive, poller).await
     }
 }
 
-pub fn watch(mut db: MultiplexedConnection, bot: Bot) -> task::JoinHandle<()> {
+fn spawn_checks(platform: Platform, mut db: MultiplexedConnection, bot: Bot) -> task::JoinHandle<()> {
     task::spawn(async move {
-        let mut interval = time::interval(Duration::from_secs(30));
+        let mut generation = GENERATION.load(Ordering::Relaxed);
+        let mut schedule = Schedule::from_env(&platform);
+        health::started(&platform, schedule.period());
+        let mut poller = Poller::new(schedule.poll_interval());
+        loop {
+            // another instance polls the platform while it holds the lease
+            if lease::is_held(&platform) {
+                check_platform(&platform, &mut db, &bot, &mut poller).await;
+            }
+            health::beat(&platform);
+            if generation != GENERATION.load(Ordering::Relaxed) {
+                generation = GENERATION.load(Ordering::Relaxed);
+                schedule = Schedule::from_env(&platform);
+                health::started(&platform, schedule.period());
+                poller.set_interval(schedule.poll_interval());
+            }
+            // the current check always finishes, the stop only cuts the wait for the next one
+            tokio::select! {
+                _ = shutdown::cancelled() => break,
+                _ = time::sleep(schedule.delay()) => ()
+            }
+        }
+    })
+}
+
+/// Run the checks of the platform in their own task, restarting it if it panics or hangs until the bot stops,
+/// and keep the lease of the platform meanwhile
+fn supervise(platform: Platform, mut db: MultiplexedConnection, bot: Bot) -> task::JoinHandle<()> {
+    shutdown::spawn(async move {
+        lease::renew(&mut db, &platform).await;
+        let mut renewal = time::interval(lease::RENEW_INTERVAL);
+        // the first tick is immediate, the lease was just renewed
+        renewal.tick().await;
         loop {
-            check(get_twitter_api().await, &mut db, &bot, Platform::TwitterSpace).await;
-            check(get_bilibili_api().await, &mut db, &bot, Platform::BilibiliLive).await;
-            interval.tick().await;
+            // the hung watcher being replaced is still registered until the new one starts
+            health::restarted(&platform);
+            let mut checks = spawn_checks(platform.clone(), db.clone(), bot.clone());
+            let mut watchdog = time::interval(Duration::from_secs(60));
+            loop {
+                tokio::select! {
+                    result = &mut checks => {
+                        if shutdown::is_cancelled() {
+                            lease::stop(&mut db, &platform).await;
+                            log::info!("{platform} watcher stopped");
+                            return;
+                        }
+                        match result {
+                            Err(e) if e.is_panic() => log::error!("{platform} watcher panicked, restarting"),
+                            _ => log::error!("{platform} watcher stopped, restarting")
+                        }
+                        break;
+                    }
+                    _ = watchdog.tick() => if health::stalled(&platform) {
+                        log::error!("{platform} watcher is hung, restarting");
+                        checks.abort();
+                        break;
+                    },
+                    _ = renewal.tick() => lease::renew(&mut db, &platform).await
+                }
+            }
+            tokio::select! {
+                _ = shutdown::cancelled() => {
+                    lease::stop(&mut db, &platform).await;
+                    return;
+                }
+                _ = time::sleep(Duration::from_secs(5)) => ()
+            }
         }
     })
 }
+
+/// Watch the subscriptions of every platform concurrently
+pub fn watch(db: MultiplexedConnection, bot: Bot) -> Vec<task::JoinHandle<()>> {
+    Platform::iter().map(|platform| supervise(platform, db.clone(), bot.clone())).collect()
+}
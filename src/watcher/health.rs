@@ -0,0 +1,71 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+
+use crate::platform::Platform;
+
+/// Minimum time without a completed check of a platform after which its watcher is considered hung
+pub const STALL_TIMEOUT: Duration = Duration::minutes(10);
+
+/// Watcher task of a platform
+#[derive(Clone, Copy)]
+struct Watcher {
+    started: DateTime<Utc>,
+    /// Longest expected time between two checks
+    period: Duration
+}
+
+lazy_static! {
+    /// Time of the last completed check of each platform
+    static ref HEARTBEATS: Mutex<HashMap<&'static str, DateTime<Utc>>> = Mutex::default();
+    static ref WATCHERS: Mutex<HashMap<&'static str, Watcher>> = Mutex::default();
+}
+
+/// Record a completed check of the platform, or a cycle spent waiting for its lease
+pub fn beat(platform: &Platform) {
+    HEARTBEATS.lock().expect("failed to lock heartbeats").insert(platform.short_name(), Utc::now());
+}
+
+pub fn last_check(platform: &Platform) -> Option<DateTime<Utc>> {
+    HEARTBEATS.lock().expect("failed to lock heartbeats").get(platform.short_name()).copied()
+}
+
+/// Register the current task as the watcher of the platform, checking it at most every `period`
+pub fn started(platform: &Platform, period: Duration) {
+    let watcher = Watcher { started: Utc::now(), period };
+    WATCHERS.lock().expect("failed to lock watchers").insert(platform.short_name(), watcher);
+}
+
+/// Count the stall timeout of the platform from now, so the watcher replacing a hung one isn't considered hung before
+/// it registers itself
+pub fn restarted(platform: &Platform) {
+    if let Some(watcher) = WATCHERS.lock().expect("failed to lock watchers").get_mut(platform.short_name()) {
+        watcher.started = Utc::now();
+    }
+}
+
+/// Whether the platform has not completed a check for [`STALL_TIMEOUT`], or three periods if longer,
+/// since its watcher started
+pub fn stalled(platform: &Platform) -> bool {
+    let Some(watcher) = WATCHERS.lock().expect("failed to lock watchers").get(platform.short_name()).copied() else {
+        return false;
+    };
+    let since = last_check(platform).map_or(watcher.started, |time| time.max(watcher.started));
+    since + STALL_TIMEOUT.max(watcher.period * 3) < Utc::now()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart() {
+        let platform = Platform::BilibiliLive;
+        let watcher = Watcher { started: Utc::now() - STALL_TIMEOUT * 2, period: Duration::seconds(30) };
+        WATCHERS.lock().unwrap().insert(platform.short_name(), watcher);
+        assert!(stalled(&platform));
+        restarted(&platform);
+        assert!(!stalled(&platform));
+    }
+}
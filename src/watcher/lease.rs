@@ -0,0 +1,87 @@
+//! Leases of the platforms in Redis, so that only one of the instances sharing the database polls each platform.
+//! The lease expires when its holder stops renewing it, and another instance takes over.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant}
+};
+
+use lazy_static::lazy_static;
+use rand::{distributions::Alphanumeric, Rng};
+use redis::{aio::MultiplexedConnection, AsyncCommands, ExistenceCheck, RedisResult, Script, SetExpiry, SetOptions};
+
+use crate::{log_utils::LogResult, platform::Platform, schema};
+
+/// Time after which the lease of a stopped instance can be taken by another
+pub const TTL: Duration = Duration::from_secs(30);
+/// Interval between the renewals of the lease, which can fail twice before it expires
+pub const RENEW_INTERVAL: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    /// Identifier of this instance in the leases
+    static ref INSTANCE_ID: String = rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect();
+    /// Extend the lease only if it is still held by the instance
+    static ref RENEW: Script = Script::new(
+        "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('PEXPIRE', KEYS[1], ARGV[2]) else return 0 end"
+    );
+    static ref RELEASE: Script = Script::new(
+        "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end"
+    );
+    /// Platforms whose lease is held by this instance, with the time the lease expires unless it is renewed
+    static ref HELD: Mutex<HashMap<&'static str, Instant>> = Mutex::default();
+}
+
+fn key(platform: &Platform) -> String {
+    schema::key(format!("lease:{}", platform.short_name()))
+}
+
+/// Renew the lease of the platform for `ttl`, or take it if nobody holds it, returning whether the holder has it
+pub async fn acquire(
+    db: &mut MultiplexedConnection, platform: &Platform, holder: &str, ttl: Duration
+) -> RedisResult<bool> {
+    let ttl = ttl.as_millis() as u64;
+    let renewed: bool = RENEW.key(key(platform)).arg(holder).arg(ttl).invoke_async(db).await?;
+    if renewed {
+        return Ok(true);
+    }
+    let options = SetOptions::default().conditional_set(ExistenceCheck::NX).with_expiration(SetExpiry::PX(ttl));
+    let acquired: Option<String> = db.set_options(key(platform), holder, options).await?;
+    Ok(acquired.is_some())
+}
+
+/// Give up the lease of the platform if the holder has it, so that another instance takes over without waiting
+pub async fn release(db: &mut MultiplexedConnection, platform: &Platform, holder: &str) -> RedisResult<bool> {
+    RELEASE.key(key(platform)).arg(holder).invoke_async(db).await
+}
+
+/// Renew or take the lease of the platform for this instance, which stops polling it if Redis can't be reached
+pub async fn renew(db: &mut MultiplexedConnection, platform: &Platform) {
+    // counted from before the request, so the lease is never considered held longer than in Redis
+    let expiry = Instant::now() + TTL;
+    let held = acquire(db, platform, &INSTANCE_ID, TTL).await
+        .log_ok(&format!("Failed to renew the {platform} lease")).unwrap_or(false);
+    let mut leases = HELD.lock().expect("failed to lock leases");
+    let changed = match held {
+        true => leases.insert(platform.short_name(), expiry).is_none(),
+        false => leases.remove(platform.short_name()).is_some()
+    };
+    if changed {
+        match held {
+            true => log::info!("Polling {platform}"),
+            false => log::info!("{platform} is polled by another instance")
+        }
+    }
+}
+
+/// Release the lease of the platform held by this instance
+pub async fn stop(db: &mut MultiplexedConnection, platform: &Platform) {
+    HELD.lock().expect("failed to lock leases").remove(platform.short_name());
+    release(db, platform, &INSTANCE_ID).await.log_ok(&format!("Failed to release the {platform} lease"));
+}
+
+/// Whether this instance holds the lease of the platform and polls it, until the lease expires if it isn't renewed
+pub fn is_held(platform: &Platform) -> bool {
+    let leases = HELD.lock().expect("failed to lock leases");
+    leases.get(platform.short_name()).is_some_and(|expiry| *expiry > Instant::now())
+}
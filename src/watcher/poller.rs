@@ -0,0 +1,114 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{apis::error::Error, subscription::Subscription};
+
+/// Longest delay between two polls of a platform or a subscription after consecutive errors
+const MAX_BACKOFF: Duration = Duration::minutes(30);
+/// Intervals between two polls of a subscription which is usually not live at this hour
+const DORMANT_INTERVALS: i32 = 4;
+
+/// Polling state of a subscription which isn't live
+struct SubscriptionState {
+    failures: u32,
+    next_poll: DateTime<Utc>
+}
+
+/// Polling schedule of the subscriptions of a platform, backing off after errors and polling subscriptions
+/// which are usually live at this hour more often than dormant ones
+pub struct Poller {
+    interval: Duration,
+    failures: u32,
+    /// The platform isn't polled until then after a rate limit or authentication error
+    suspended_until: Option<DateTime<Utc>>,
+    subs: HashMap<String, SubscriptionState>,
+    /// Errors already reported to the owner
+    reported: HashSet<String>
+}
+
+impl Poller {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, failures: 0, suspended_until: None, subs: HashMap::new(), reported: HashSet::new() }
+    }
+
+    /// Poll every `interval` from now on, e.g. after the configuration was reloaded
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Delay after the given number of consecutive failures, doubled after each failure
+    fn backoff(&self, failures: u32) -> Duration {
+        (self.interval * 2i32.saturating_pow(failures.min(16))).min(MAX_BACKOFF)
+    }
+
+    pub fn suspended(&self) -> bool {
+        self.suspended_until.is_some_and(|time| time > Utc::now())
+    }
+
+    /// Whether the subscription should be polled in this cycle
+    pub fn due(&self, sub: &Subscription) -> bool {
+        self.subs.get(&sub.to_db_string()).is_none_or(|state| state.next_poll <= Utc::now())
+    }
+
+    /// Suspend polling after an error affecting the whole platform, until the rate limit resets if it is known
+    pub fn platform_failed(&mut self, error: &Error) {
+        self.failures += 1;
+        let until = match error {
+            Error::RateLimited(Some(reset)) => *reset,
+            _ => Utc::now() + self.backoff(self.failures)
+        };
+        log::warn!("Suspending polling until {}: {error}", until.format("%H:%M:%S UTC"));
+        self.suspended_until = Some(until);
+    }
+
+    pub fn platform_succeeded(&mut self) {
+        self.failures = 0;
+    }
+
+    pub fn failed(&mut self, sub: &Subscription) {
+        let failures = self.subs.get(&sub.to_db_string()).map_or(0, |state| state.failures) + 1;
+        let next_poll = Utc::now() + self.backoff(failures);
+        self.subs.insert(sub.to_db_string(), SubscriptionState { failures, next_poll });
+    }
+
+    /// Whether the error should be reported to the owner, only the first time an error hinting at an API change
+    /// occurs so a broken endpoint doesn't flood the owner with one message per subscription
+    pub fn report(&mut self, error: &Error) -> bool {
+        error.needs_attention() && self.reported.insert(error.to_string())
+    }
+
+    /// Schedule the next poll of the subscription, which is due in the next cycle if it is usually live at this hour
+    pub fn polled(&mut self, sub: &Subscription, usually_live: bool) {
+        let intervals = if usually_live { 1 } else { DORMANT_INTERVALS };
+        // the next cycle starts an interval later, so the subscription is polled again `intervals` cycles later
+        let next_poll = Utc::now() + self.interval * (intervals - 1);
+        self.subs.insert(sub.to_db_string(), SubscriptionState { failures: 0, next_poll });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::{Platform, User};
+
+    #[test]
+    fn backoff() {
+        let sub = Subscription {
+            platform: Platform::BilibiliLive,
+            user: User { id: "12345".to_owned(), username: "Telescope".to_owned() }
+        };
+        let mut poller = Poller::new(Duration::seconds(30));
+        assert!(poller.due(&sub));
+        poller.polled(&sub, true);
+        assert!(poller.due(&sub));
+        poller.polled(&sub, false);
+        assert!(!poller.due(&sub));
+        poller.failed(&sub);
+        assert!(!poller.due(&sub));
+        assert_eq!(poller.backoff(1), Duration::minutes(1));
+        assert_eq!(poller.backoff(20), MAX_BACKOFF);
+        poller.platform_failed(&Error::RateLimited(Some(Utc::now() + Duration::minutes(5))));
+        assert!(poller.suspended());
+    }
+}
@@ -0,0 +1,222 @@
+use std::{fmt::Display, future::Future, time::Duration};
+
+use chrono::Utc;
+use lazy_static::lazy_static;
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use strum_macros::Display;
+use tokio::time;
+use webhook::WebhookSink;
+
+use crate::{
+    apis::{LiveState, Metadata},
+    history,
+    log_utils::LogResult,
+    metrics::{self, CountError},
+    platform::Platform,
+    shutdown,
+    subscription::Subscription,
+    templates::Placeholder
+};
+
+pub mod discord;
+pub mod matrix;
+pub mod targets;
+pub mod webhook;
+
+lazy_static! {
+    static ref CLIENT: Client = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build().expect("Failed to create sink client");
+}
+
+#[derive(Clone, Copy, Display, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum EventKind {
+    Start,
+    End,
+    /// The title or cover of a running live changed
+    Change
+}
+
+/// Live event delivered to the sinks, timestamps are in seconds since the Unix epoch
+#[derive(Clone, Serialize)]
+pub struct LiveEvent {
+    pub event: EventKind,
+    pub platform: &'static str,
+    pub user_id: String,
+    pub username: String,
+    pub creator: String,
+    pub live_id: String,
+    pub state: String,
+    pub title: String,
+    pub old_title: Option<String>,
+    pub url: String,
+    pub profile_url: Option<String>,
+    pub cover: Option<String>,
+    pub start_time: Option<i64>,
+    pub time: i64
+}
+
+impl LiveEvent {
+    pub fn new(event: EventKind, live: &impl Metadata) -> Self {
+        let sub = live.to_sub();
+        let mut placeholders = live.placeholders();
+        Self {
+            event,
+            platform: sub.platform.short_name(),
+            profile_url: placeholders.remove(&Placeholder::ProfileUrl),
+            creator: placeholders.remove(&Placeholder::Creator).unwrap_or_else(|| sub.user.username.clone()),
+            user_id: sub.user.id,
+            username: sub.user.username,
+            live_id: live.get_id().to_string(),
+            state: match live.get_state() {
+                LiveState::Running => "running".to_owned(),
+                LiveState::Ended => "ended".to_owned(),
+                LiveState::TimedOut => "timed_out".to_owned(),
+                LiveState::Unknown(state) => state.to_owned()
+            },
+            title: live.get_title().to_owned(),
+            old_title: None,
+            url: live.get_url().to_string(),
+            cover: live.get_cover().map(ToString::to_string),
+            start_time: live.get_start_time().map(|t| t.timestamp()),
+            time: Utc::now().timestamp()
+        }
+    }
+
+    /// Plain text description of the event for the sinks posting messages
+    pub fn summary(&self) -> String {
+        let platform = Platform::iter().find(|p| p.short_name() == self.platform)
+            .map_or_else(|| self.platform.to_owned(), |p| p.to_string());
+        match self.event {
+            EventKind::Start => format!("{}'s {platform} started", self.creator),
+            EventKind::End => format!("{}'s {platform} ended", self.creator),
+            EventKind::Change => match &self.old_title {
+                Some(old_title) => format!("{} changed the title of the {platform} from {old_title}", self.creator),
+                None => format!("{} changed the cover of the {platform}", self.creator)
+            }
+        }
+    }
+}
+
+pub enum SinkError {
+    /// The target may accept the event later, e.g. it is overloaded or unreachable
+    Retryable(String),
+    Fatal(String)
+}
+
+impl Display for SinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinkError::Retryable(e) | SinkError::Fatal(e) => f.write_str(e)
+        }
+    }
+}
+
+impl SinkError {
+    /// Classify the response of a target, server errors and rate limits being retryable
+    pub fn from_status(status: StatusCode) -> Result<(), Self> {
+        match status {
+            s if s.is_success() => Ok(()),
+            s if s.is_server_error() || s == StatusCode::TOO_MANY_REQUESTS => Err(SinkError::Retryable(s.to_string())),
+            s => Err(SinkError::Fatal(s.to_string()))
+        }
+    }
+}
+
+impl From<reqwest::Error> for SinkError {
+    fn from(value: reqwest::Error) -> Self {
+        SinkError::Retryable(value.to_string())
+    }
+}
+
+/// Destination other than Telegram receiving live events
+pub trait Sink: Send + Sync + 'static {
+    /// Name of the target used in logs
+    fn name(&self) -> String;
+    fn send(&self, event: &LiveEvent) -> impl Future<Output = Result<(), SinkError>> + Send;
+}
+
+/// Delivery attempts of an event before giving up
+const ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled after each attempt
+const BACKOFF: Duration = Duration::from_secs(2);
+
+/// Send the event to the sink, retrying with exponential backoff. Returns whether it was delivered.
+pub async fn deliver(sink: &impl Sink, event: &LiveEvent, backoff: Duration) -> bool {
+    for attempt in 0..ATTEMPTS {
+        match sink.send(event).await {
+            Ok(()) => return true,
+            Err(SinkError::Retryable(e)) if attempt + 1 < ATTEMPTS => {
+                log::warn!("Failed to send {} event to {}, retrying: {e}", event.event, sink.name());
+                time::sleep(backoff * 2u32.pow(attempt)).await;
+            }
+            Err(e) => {
+                log::error!("Failed to send {} event to {}: {e}", event.event, sink.name());
+                break;
+            }
+        }
+    }
+    false
+}
+
+/// Deliver the event in the background, counting the notification in the metrics of the channel
+fn spawn(channel: &'static str, sink: impl Sink, event: &LiveEvent) {
+    let event = event.clone();
+    shutdown::spawn(async move { metrics::notification(channel, deliver(&sink, &event, BACKOFF).await) });
+}
+
+/// Record the event in the history, and send it in the background to the global webhook,
+/// the webhooks of the chats subscribed to it and the targets bound to the subscription
+pub async fn notify(db: &mut MultiplexedConnection, sub: &Subscription, event: LiveEvent) {
+    history::record(db, sub, &event).await.count_err().log_ok("Failed to record live event");
+    if let Some(webhook) = WebhookSink::global() {
+        spawn("webhook", webhook, &event);
+    }
+    let chats: Vec<String> = db.hkeys(sub.key()).await.count_err().log_ok("Failed to get subscribers").unwrap_or_default();
+    for chat_id in chats {
+        if let Some(Some(webhook)) = webhook::get(db, &chat_id).await.count_err().log_ok("Failed to get webhook") {
+            spawn("webhook", webhook, &event);
+        }
+    }
+    for target in targets::bound(db, sub).await.count_err().log_ok("Failed to get targets").unwrap_or_default() {
+        spawn("target", target, &event);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn event() -> LiveEvent {
+        LiveEvent {
+            event: EventKind::Start,
+            platform: "twitter",
+            user_id: "12345".to_owned(),
+            username: "telescope".to_owned(),
+            creator: "Telescope".to_owned(),
+            live_id: "1OdKrjzyLDyKX".to_owned(),
+            state: "running".to_owned(),
+            title: "Test".to_owned(),
+            old_title: None,
+            url: "https://twitter.com/i/spaces/1OdKrjzyLDyKX".to_owned(),
+            profile_url: Some("https://twitter.com/telescope".to_owned()),
+            cover: None,
+            start_time: Some(1735732800),
+            time: 1735732800
+        }
+    }
+
+    #[test]
+    fn summary() {
+        let mut event = event();
+        assert_eq!(event.summary(), "Telescope's Twitter Space started");
+        event.event = EventKind::Change;
+        event.old_title = Some("Old".to_owned());
+        assert_eq!(event.summary(), "Telescope changed the title of the Twitter Space from Old");
+    }
+}
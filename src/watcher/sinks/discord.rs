@@ -0,0 +1,83 @@
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{EventKind, LiveEvent, Sink, SinkError, CLIENT};
+
+/// Discord channel webhook, posting events as embeds
+#[derive(Serialize, Deserialize)]
+pub struct DiscordSink {
+    pub webhook_url: String
+}
+
+impl DiscordSink {
+    const COLOR_START: u32 = 0x57F287;
+    const COLOR_END: u32 = 0x99AAB5;
+    const COLOR_CHANGE: u32 = 0xFEE75C;
+
+    pub fn message(event: &LiveEvent) -> Value {
+        let color = match event.event {
+            EventKind::Start => Self::COLOR_START,
+            EventKind::End => Self::COLOR_END,
+            EventKind::Change => Self::COLOR_CHANGE
+        };
+        json!({
+            "username": "Telescope",
+            "embeds": [{
+                "title": event.title,
+                "url": event.url,
+                "description": event.summary(),
+                "color": color,
+                "author": { "name": event.creator, "url": event.profile_url },
+                // the cover is what Telegram notifications attach for platforms with one
+                "thumbnail": event.cover.as_ref().map(|cover| json!({ "url": cover })),
+                "timestamp": DateTime::from_timestamp(event.time, 0).map(|time| time.to_rfc3339())
+            }]
+        })
+    }
+}
+
+impl Sink for DiscordSink {
+    fn name(&self) -> String {
+        "Discord webhook".to_owned()
+    }
+
+    async fn send(&self, event: &LiveEvent) -> Result<(), SinkError> {
+        let response = CLIENT.post(&self.webhook_url).json(&Self::message(event)).send().await?;
+        SinkError::from_status(response.status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wiremock::{
+        matchers::{body_partial_json, method, path},
+        Mock,
+        MockServer,
+        ResponseTemplate
+    };
+
+    use super::*;
+    use crate::watcher::sinks::{deliver, tests::event};
+
+    #[tokio::test]
+    async fn embed() {
+        let server = MockServer::start().await;
+        let mut event = event();
+        event.cover = Some("https://example.com/cover.jpg".to_owned());
+        Mock::given(method("POST")).and(path("/api/webhooks/1/token"))
+            .and(body_partial_json(json!({
+                "embeds": [{
+                    "title": "Test",
+                    "url": "https://twitter.com/i/spaces/1OdKrjzyLDyKX",
+                    "description": "Telescope's Twitter Space started",
+                    "thumbnail": { "url": "https://example.com/cover.jpg" }
+                }]
+            })))
+            .respond_with(ResponseTemplate::new(204)).expect(1).mount(&server).await;
+        let sink = DiscordSink { webhook_url: format!("{}/api/webhooks/1/token", server.uri()) };
+        assert!(deliver(&sink, &event, Duration::ZERO).await);
+    }
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use url::Url;
+
+use super::{LiveEvent, Sink, SinkError, CLIENT};
+
+/// Matrix room, receiving events as notices sent through the client-server API
+#[derive(Serialize, Deserialize)]
+pub struct MatrixSink {
+    pub homeserver: String,
+    pub room_id: String,
+    pub access_token: String
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl MatrixSink {
+    /// URL of the message event, the transaction ID making retries of the same event idempotent
+    fn url(&self, event: &LiveEvent) -> Result<Url, SinkError> {
+        let mut url: Url = self.homeserver.parse().map_err(|e: url::ParseError| SinkError::Fatal(e.to_string()))?;
+        let txn_id = format!("telescope-{}-{}-{}", event.live_id, event.event, event.time);
+        url.path_segments_mut().map_err(|_| SinkError::Fatal("Invalid homeserver URL".to_owned()))?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3", "rooms", &self.room_id, "send", "m.room.message", &txn_id]);
+        Ok(url)
+    }
+}
+
+impl Sink for MatrixSink {
+    fn name(&self) -> String {
+        format!("Matrix room {}", self.room_id)
+    }
+
+    async fn send(&self, event: &LiveEvent) -> Result<(), SinkError> {
+        let summary = event.summary();
+        let content = json!({
+            "msgtype": "m.notice",
+            "body": format!("{summary}\n{}\n{}", event.title, event.url),
+            "format": "org.matrix.custom.html",
+            "formatted_body": format!(
+                "{}<br><a href=\"{}\">{}</a>",
+                escape_html(&summary),
+                escape_html(&event.url),
+                escape_html(&event.title)
+            )
+        });
+        let response = CLIENT.put(self.url(event)?).bearer_auth(&self.access_token).json(&content).send().await?;
+        SinkError::from_status(response.status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wiremock::{
+        matchers::{body_partial_json, header, method, path},
+        Mock,
+        MockServer,
+        ResponseTemplate
+    };
+
+    use super::*;
+    use crate::watcher::sinks::{deliver, tests::event};
+
+    #[tokio::test]
+    async fn notice() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path(
+                "/_matrix/client/v3/rooms/!room:example.org/send/m.room.message/telescope-1OdKrjzyLDyKX-start-1735732800"
+            ))
+            .and(header("Authorization", "Bearer token"))
+            .and(body_partial_json(json!({
+                "msgtype": "m.notice",
+                "body": "Telescope's Twitter Space started\nTest\nhttps://twitter.com/i/spaces/1OdKrjzyLDyKX"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$event" })))
+            .expect(1).mount(&server).await;
+        let sink = MatrixSink {
+            homeserver: format!("{}/", server.uri()),
+            room_id: "!room:example.org".to_owned(),
+            access_token: "token".to_owned()
+        };
+        assert!(deliver(&sink, &event(), Duration::ZERO).await);
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisResult};
+use serde::{Deserialize, Serialize};
+
+use super::{discord::DiscordSink, matrix::MatrixSink, LiveEvent, Sink, SinkError};
+use crate::{schema, subscription::Subscription};
+
+/// Notification target managed by the bot owner, stored as JSON in the `targets` hash
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Target {
+    Discord(DiscordSink),
+    Matrix(MatrixSink)
+}
+
+impl Target {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Target::Discord(_) => "discord",
+            Target::Matrix(_) => "matrix"
+        }
+    }
+}
+
+impl Sink for Target {
+    fn name(&self) -> String {
+        match self {
+            Target::Discord(sink) => sink.name(),
+            Target::Matrix(sink) => sink.name()
+        }
+    }
+
+    async fn send(&self, event: &LiveEvent) -> Result<(), SinkError> {
+        match self {
+            Target::Discord(sink) => sink.send(event).await,
+            Target::Matrix(sink) => sink.send(event).await
+        }
+    }
+}
+
+/// Key of the set of subscriptions bound to the target
+fn target_key(name: impl Display) -> String {
+    schema::key(format!("target:{name}"))
+}
+
+/// Key of the set of targets bound to the subscription
+fn sub_key(sub: &Subscription) -> String {
+    schema::key(format!("targets:{}", sub.to_db_string()))
+}
+
+pub async fn list(db: &mut MultiplexedConnection) -> RedisResult<Vec<(String, Option<Target>, usize)>> {
+    let targets: Vec<(String, String)> = db.hgetall(schema::key("targets")).await?;
+    let mut result = vec![];
+    for (name, target) in targets {
+        let count = db.scard(target_key(&name)).await?;
+        result.push((name, serde_json::from_str(&target).ok(), count));
+    }
+    Ok(result)
+}
+
+pub async fn add(db: &mut MultiplexedConnection, name: &str, target: &Target) -> RedisResult<()> {
+    let target = serde_json::to_string(target).expect("Targets are serializable");
+    db.hset(schema::key("targets"), name, target).await
+}
+
+/// Remove the target and unbind it from all its subscriptions
+pub async fn remove(db: &mut MultiplexedConnection, name: &str) -> RedisResult<bool> {
+    let subs: Vec<Subscription> = db.smembers(target_key(name)).await?;
+    unbind(db, name, &subs).await?;
+    let removed: usize = db.hdel(schema::key("targets"), name).await?;
+    Ok(removed > 0)
+}
+
+pub async fn exists(db: &mut MultiplexedConnection, name: &str) -> RedisResult<bool> {
+    db.hexists(schema::key("targets"), name).await
+}
+
+/// Bind the target to the subscriptions, polling them like Telegram subscriptions
+pub async fn bind(db: &mut MultiplexedConnection, name: &str, subs: &[Subscription]) -> RedisResult<()> {
+    let mut pipe = redis::pipe();
+    let pipe = pipe.atomic();
+    for sub in subs {
+        pipe.hset_nx(schema::key("subs"), sub, "").sadd(sub_key(sub), name).sadd(target_key(name), sub);
+    }
+    pipe.exec_async(db).await
+}
+
+pub async fn unbind(db: &mut MultiplexedConnection, name: &str, subs: &[Subscription]) -> RedisResult<()> {
+    for sub in subs {
+        let (_, _, targets, chats): ((), (), usize, usize) = redis::pipe().atomic()
+            .srem(sub_key(sub), name).srem(target_key(name), sub).scard(sub_key(sub)).hlen(sub.key())
+            .query_async(db).await?;
+        if targets == 0 && chats == 0 {
+            let _: () = db.hdel(schema::key("subs"), sub).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Number of targets bound to the subscription
+pub async fn count(db: &mut MultiplexedConnection, sub: &Subscription) -> RedisResult<usize> {
+    db.scard(sub_key(sub)).await
+}
+
+/// Targets bound to the subscription
+pub async fn bound(db: &mut MultiplexedConnection, sub: &Subscription) -> RedisResult<Vec<Target>> {
+    let names: Vec<String> = db.smembers(sub_key(sub)).await?;
+    if names.is_empty() {
+        return Ok(vec![]);
+    }
+    let targets: Vec<Option<String>> = redis::cmd("HMGET").arg(schema::key("targets")).arg(&names).query_async(db).await?;
+    Ok(targets.into_iter().flatten().filter_map(|target| {
+        serde_json::from_str(&target).inspect_err(|e| log::error!("Invalid target: {e}")).ok()
+    }).collect())
+}
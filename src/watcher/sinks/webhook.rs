@@ -0,0 +1,257 @@
+use std::{
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration
+};
+
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use redis::{aio::MultiplexedConnection, AsyncCommands, RedisResult};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+    Client
+};
+use sha2::Sha256;
+use tokio::net::lookup_host;
+use url::{Host, Url};
+
+use super::{LiveEvent, Sink, SinkError, CLIENT};
+use crate::{config, schema};
+
+/// Header holding the HMAC-SHA256 of the request body, as `sha256=<hex digest>`
+pub const SIGNATURE_HEADER: &str = "X-Telescope-Signature";
+pub const EVENT_HEADER: &str = "X-Telescope-Event";
+
+lazy_static! {
+    /// Client of the webhooks set by chats, which only connects to public addresses and doesn't follow redirects,
+    /// so a chat can't make the bot reach the local network, even if its host resolves to another address later
+    static ref PUBLIC_CLIENT: Client = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(Policy::none())
+        .build().expect("Failed to create webhook client");
+}
+
+/// Whether the address can be reached from the internet, and not only from the local network or the host
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            // 100.64.0.0/10, shared by the customers of a carrier-grade NAT
+            let shared = ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64;
+            !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()
+                || shared)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(ip.into()),
+            None => !(ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local())
+        }
+    }
+}
+
+/// Whether the host of the URL only has public addresses
+pub async fn is_public(url: &Url) -> bool {
+    let ips: Vec<IpAddr> = match url.host() {
+        Some(Host::Ipv4(ip)) => vec![ip.into()],
+        Some(Host::Ipv6(ip)) => vec![ip.into()],
+        Some(Host::Domain(domain)) => match lookup_host((domain, url.port_or_known_default().unwrap_or(443))).await {
+            Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+            Err(_) => return false
+        },
+        None => return false
+    };
+    !ips.is_empty() && ips.into_iter().all(is_public_ip)
+}
+
+/// Resolver dropping the private, loopback and link-local addresses
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0)).await?
+                .filter(|addr| is_public_ip(addr.ip())).collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// HTTP endpoint receiving live events as JSON
+pub struct WebhookSink {
+    url: Url,
+    secret: String,
+    /// Set by a chat, which may only send events to public addresses
+    public_only: bool
+}
+
+impl WebhookSink {
+    pub fn new(url: Url, secret: String) -> Self {
+        Self { url, secret, public_only: false }
+    }
+
+    /// Webhook set by a chat with `/webhook`
+    pub fn chat(url: Url, secret: String) -> Self {
+        Self { url, secret, public_only: true }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Webhook receiving the events of every subscription, configured by `WEBHOOK_URL` and `WEBHOOK_SECRET`,
+    /// which is required so the requests can be verified
+    pub fn global() -> Option<Self> {
+        let url = config::var("WEBHOOK_URL").ok()?.parse()
+            .inspect_err(|e| log::error!("Invalid WEBHOOK_URL: {e}")).ok()?;
+        let Some(secret) = global_secret() else {
+            log::error!("WEBHOOK_SECRET is not set, the events are not sent to WEBHOOK_URL");
+            return None;
+        };
+        Some(Self::new(url, secret))
+    }
+
+    pub fn sign(&self, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("HMAC accepts keys of any size");
+        mac.update(body);
+        format!("sha256={}", base16ct::lower::encode_string(&mac.finalize().into_bytes()))
+    }
+}
+
+impl Sink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    async fn send(&self, event: &LiveEvent) -> Result<(), SinkError> {
+        let body = serde_json::to_vec(event).map_err(|e| SinkError::Fatal(e.to_string()))?;
+        // the resolver of the client only checks domains, not IP addresses
+        if self.public_only && !is_public(&self.url).await {
+            return Err(SinkError::Fatal("not a public address".to_owned()));
+        }
+        let client = if self.public_only { &*PUBLIC_CLIENT } else { &*CLIENT };
+        let response = client.post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, self.sign(&body))
+            .header(EVENT_HEADER, event.event.to_string())
+            .body(body)
+            .send().await?;
+        SinkError::from_status(response.status())
+    }
+}
+
+fn key(chat_id: impl Display) -> String {
+    schema::key(format!("webhook:{chat_id}"))
+}
+
+/// Webhook of the chat, stored in the `webhook:<chat_id>` hash
+pub async fn get(db: &mut MultiplexedConnection, chat_id: impl Display) -> RedisResult<Option<WebhookSink>> {
+    let (url, secret): (Option<String>, Option<String>) = redis::pipe()
+        .hget(key(&chat_id), "url").hget(key(&chat_id), "secret")
+        .query_async(db).await?;
+    Ok(url.and_then(|url| url.parse().ok()).map(|url| WebhookSink::chat(url, secret.unwrap_or_default())))
+}
+
+/// Value of `WEBHOOK_SECRET`, unless it is empty
+pub fn global_secret() -> Option<String> {
+    config::var("WEBHOOK_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+pub async fn set(db: &mut MultiplexedConnection, chat_id: impl Display, url: &Url, secret: &str) -> RedisResult<()> {
+    db.hset_multiple(key(chat_id), &[("url", url.as_str()), ("secret", secret)]).await
+}
+
+pub async fn remove(db: &mut MultiplexedConnection, chat_id: impl Display) -> RedisResult<()> {
+    db.del(key(chat_id)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wiremock::{
+        matchers::{header, header_exists, method, path},
+        Mock,
+        MockServer,
+        ResponseTemplate
+    };
+
+    use super::*;
+    use crate::watcher::sinks::{deliver, tests::event};
+
+    fn sink(server: &MockServer) -> WebhookSink {
+        WebhookSink::new(format!("{}/hook", server.uri()).parse().unwrap(), "secret".to_owned())
+    }
+
+    #[tokio::test]
+    async fn signed_payload() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook")).and(header(EVENT_HEADER, "start")).and(header_exists(SIGNATURE_HEADER))
+            .respond_with(ResponseTemplate::new(204)).expect(1).mount(&server).await;
+        let sink = sink(&server);
+        assert!(deliver(&sink, &event(), Duration::ZERO).await);
+        let request = &server.received_requests().await.unwrap()[0];
+        assert_eq!(request.headers[SIGNATURE_HEADER], sink.sign(&request.body));
+        let payload: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(payload["event"], "start");
+        assert_eq!(payload["live_id"], "1OdKrjzyLDyKX");
+        assert_eq!(payload["start_time"], 1735732800);
+    }
+
+    #[test]
+    fn signature() {
+        let sink = WebhookSink::new("https://example.com".parse().unwrap(), "key".to_owned());
+        assert_eq!(
+            sink.sign(b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[tokio::test]
+    async fn public_addresses() {
+        for url in [
+            "https://127.0.0.1/",
+            "https://10.0.0.1/",
+            "https://192.168.1.1/",
+            "https://169.254.169.254/latest/meta-data/",
+            "https://100.64.0.1/",
+            "https://[::1]/",
+            "https://[fd00::1]/",
+            "https://[fe80::1]/",
+            "https://[::ffff:127.0.0.1]/",
+            "https://localhost/"
+        ] {
+            assert!(!is_public(&url.parse().unwrap()).await, "{url}");
+        }
+        assert!(is_public(&"https://93.184.215.14/hook".parse().unwrap()).await);
+        assert!(is_public(&"https://[2606:2800:21f:cb07:6820:80da:af6b:8b2c]/".parse().unwrap()).await);
+    }
+
+    #[tokio::test]
+    async fn rejects_private_chat_webhooks() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(204)).expect(0).mount(&server).await;
+        let sink = WebhookSink::chat(format!("{}/hook", server.uri()).parse().unwrap(), "secret".to_owned());
+        assert!(!deliver(&sink, &event(), Duration::ZERO).await);
+    }
+
+    #[tokio::test]
+    async fn retries() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2).expect(2).mount(&server).await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).expect(1).mount(&server).await;
+        assert!(deliver(&sink(&server), &event(), Duration::from_millis(10)).await);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_client_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(410)).expect(1).mount(&server).await;
+        assert!(!deliver(&sink(&server), &event(), Duration::from_millis(10)).await);
+    }
+}
@@ -0,0 +1,385 @@
+//! Subscription of a user, then notification of the start and of the end of a live, from the updates received by the
+//! bot to the messages it sends and the database state
+
+use std::env;
+
+use chrono::Duration;
+use mock::{
+    bilibili::{self, FakeBilibili, ROOM_ID},
+    telegram::{self, FakeTelegram, CHAT_ID, OWNER_ID},
+    twitter::{self, FakeTwitter, GUEST_ID, SCREEN_NAME, SPACE_ID}
+};
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use telescope::{
+    apis::{get_bilibili_api, get_twitter_api},
+    platform::Platform,
+    schema,
+    watcher::{self, lease, poller::Poller}
+};
+use tokio::{sync::Mutex, time};
+
+mod mock;
+
+/// The tests share the database
+static DATABASE: Mutex<()> = Mutex::const_new(());
+
+/// Subscribe to the user of the URL, `user` being how the bot lists it
+async fn subscribe(telegram: &FakeTelegram, db: &mut MultiplexedConnection, url: &str, sub: &str, user: &str) {
+    telegram.dispatch(db, telegram::text(1, &format!("/sub {url}"))).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method, "sendMessage");
+    assert_eq!(
+        calls[0].params["text"],
+        format!("Please confirm that you want to subscribe to the following users:\n1\\. {user}")
+    );
+    let message_id = 100;
+    let pending: Vec<String> = db.lrange(format!("telescope:pending:{CHAT_ID}:{message_id}"), 0, -1).await.unwrap();
+    assert_eq!(pending, [sub]);
+
+    telegram.dispatch(db, telegram::callback(2, message_id, "subscribe")).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method, "editMessageText");
+    assert_eq!(calls[0].params["message_id"], message_id);
+    assert_eq!(calls[0].params["text"], format!("You have successfully subscribed to:\n1\\. {user}"));
+    let live_id: Option<String> = db.hget("telescope:subs", sub).await.unwrap();
+    assert_eq!(live_id.as_deref(), Some(""));
+    let subs: Vec<String> = db.smembers(format!("telescope:chat:{CHAT_ID}")).await.unwrap();
+    assert_eq!(subs, [sub]);
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis database at TEST_REDIS_URL"]
+async fn bilibili() {
+    let _database = DATABASE.lock().await;
+    let mut db = mock::redis().await;
+    let telegram = FakeTelegram::start().await;
+    let bilibili = FakeBilibili::start(bilibili::room(true)).await;
+    env::set_var("BILIBILI_API_URL", bilibili.api_url());
+    env::set_var("BILIBILI_NAV_URL", bilibili.nav_url());
+    let sub = "Bilibili Live:7654321:望远镜";
+    subscribe(&telegram, &mut db, &format!("live.bilibili.com/{ROOM_ID}"), sub, "Bilibili Live: *望远镜*").await;
+
+    let api = get_bilibili_api().await;
+    let mut poller = Poller::new(Duration::seconds(30));
+    // only the instance holding the lease sends notifications
+    watcher::check(api, &mut db, &telegram.bot(), Platform::BilibiliLive, &mut poller).await;
+    assert!(telegram.calls().await.is_empty());
+    lease::renew(&mut db, &Platform::BilibiliLive).await;
+    watcher::check(api, &mut db, &telegram.bot(), Platform::BilibiliLive, &mut poller).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method, "sendPhoto");
+    assert_eq!(calls[0].params["chat_id"], CHAT_ID);
+    assert_eq!(calls[0].params["photo"], "https://i0.hdslb.com/bfs/live/new_room_cover/abcdef0123456789.jpg");
+    assert_eq!(
+        calls[0].params["caption"],
+        "*望远镜* \\([1234567](https://space.bilibili.com/1234567)\\)'s Bilibili Live started\n\
+        [晚间直播](https://live.bilibili.com/7654321)"
+    );
+    let live_id: Option<String> = db.hget("telescope:subs", sub).await.unwrap();
+    assert_eq!(live_id.as_deref(), Some(ROOM_ID));
+    let message_id: Option<i32> = db.hget(format!("telescope:sub:{sub}"), CHAT_ID).await.unwrap();
+    assert_eq!(message_id, Some(101));
+
+    bilibili.set_room(bilibili::room(false)).await;
+    watcher::check(api, &mut db, &telegram.bot(), Platform::BilibiliLive, &mut poller).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method, "sendMessage");
+    assert_eq!(calls[0].params["reply_parameters"]["message_id"], 101);
+    // the duration is counted until now
+    let text = calls[0].params["text"].as_str().unwrap();
+    assert!(text.starts_with(
+        "*望远镜* \\([1234567](https://space.bilibili.com/1234567)\\)'s Bilibili Live ended\n\
+        [晚间直播](https://live.bilibili.com/7654321)\nDuration: "
+    ));
+    assert!(text.ends_with("\nViewers: 2345"));
+    let live_id: Option<String> = db.hget("telescope:subs", sub).await.unwrap();
+    assert_eq!(live_id.as_deref(), Some(""));
+    let message_id: Option<i32> = db.hget(format!("telescope:sub:{sub}"), CHAT_ID).await.unwrap();
+    assert_eq!(message_id, Some(0));
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis database at TEST_REDIS_URL"]
+async fn twitter() {
+    let _database = DATABASE.lock().await;
+    let mut db = mock::redis().await;
+    let telegram = FakeTelegram::start().await;
+    let twitter = FakeTwitter::start(twitter::space(true)).await;
+    env::set_var("TWITTER_API_URL", twitter.api_url());
+    env::set_var("TWITTER_WEB_URL", twitter.web_url());
+    env::set_var("TWITTER_AUTH_TOKEN", "auth_token");
+    env::set_var("TWITTER_CSRF_TOKEN", "csrf_token");
+    let sub = "Twitter Space:123456789:telescope_dev";
+    subscribe(&telegram, &mut db, &format!("x.com/{SCREEN_NAME}"), sub, "Twitter Space: *telescope\\_dev*").await;
+
+    let api = get_twitter_api().await;
+    let mut poller = Poller::new(Duration::seconds(30));
+    lease::renew(&mut db, &Platform::TwitterSpace).await;
+    watcher::check(api, &mut db, &telegram.bot(), Platform::TwitterSpace, &mut poller).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method, "sendDocument");
+    assert_eq!(calls[0].params["chat_id"], CHAT_ID);
+    assert_eq!(calls[0].params["document"], format!("{SPACE_ID}.json"));
+    assert_eq!(
+        calls[0].params["caption"],
+        "*Telescope* \\([@telescope\\_dev](https://twitter.com/telescope_dev)\\)'s Twitter Space started\n\
+        [Morning chat](https://twitter.com/i/spaces/1YqKDqDXAbwKV)\n```shell\ntwspace_dl -ei \
+        https://twitter.com/i/spaces/1YqKDqDXAbwKV -f https://prod-fastly-ap-northeast-1.video.pscp.tv/Transcoding/v1/\
+        hls/abc123/non_transcode/ap-northeast-1/periscope-replay-direct-prod-ap-northeast-1-public/audio-space/\
+        master_playlist.m3u8\n```"
+    );
+    let live_id: Option<String> = db.hget("telescope:subs", sub).await.unwrap();
+    assert_eq!(live_id.as_deref(), Some(SPACE_ID));
+    let message_id: Option<i32> = db.hget(format!("telescope:sub:{sub}"), CHAT_ID).await.unwrap();
+    assert_eq!(message_id, Some(101));
+
+    twitter.set_space(twitter::space(false)).await;
+    watcher::check(api, &mut db, &telegram.bot(), Platform::TwitterSpace, &mut poller).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method, "sendMessage");
+    assert_eq!(calls[0].params["reply_parameters"]["message_id"], 101);
+    assert_eq!(
+        calls[0].params["text"],
+        "*Telescope* \\([@telescope\\_dev](https://twitter.com/telescope_dev)\\)'s Twitter Space ended\n\
+        [Morning chat](https://twitter.com/i/spaces/1YqKDqDXAbwKV)\nDuration: 1:00:00\nListeners: 42\n\
+        Replay: [available](https://twitter.com/i/spaces/1YqKDqDXAbwKV)\n\
+        ```shell\ntwspace_dl -ei https://twitter.com/i/spaces/1YqKDqDXAbwKV\n```"
+    );
+    let live_id: Option<String> = db.hget("telescope:subs", sub).await.unwrap();
+    assert_eq!(live_id.as_deref(), Some(""));
+
+    // a Space the subscribed user only speaks in isn't a live of theirs, and its creator isn't subscribed
+    let guest = format!("Twitter Space:{GUEST_ID}:telescope_guest");
+    redis::pipe()
+        .hdel("telescope:subs", sub)
+        .hset("telescope:subs", &guest, "")
+        .hset(format!("telescope:sub:{guest}"), CHAT_ID, 0)
+        .exec_async(&mut db).await.unwrap();
+    twitter.set_appearance().await;
+    watcher::check(api, &mut db, &telegram.bot(), Platform::TwitterSpace, &mut poller).await;
+    assert!(telegram.calls().await.is_empty());
+    let subs: Vec<String> = db.hkeys("telescope:subs").await.unwrap();
+    assert_eq!(subs, [guest.as_str()]);
+    let live: bool = db.exists(format!("telescope:live:Twitter Space:{SPACE_ID}")).await.unwrap();
+    assert!(!live);
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis database at TEST_REDIS_URL"]
+async fn ban() {
+    let _database = DATABASE.lock().await;
+    let mut db = mock::redis().await;
+    let telegram = FakeTelegram::start().await;
+    env::set_var("BOT_OWNER", OWNER_ID.to_string());
+    // subscribed like after the confirmation, the API clients being bound to the mock servers of the other tests
+    let sub = "Bilibili Live:7654321:望远镜";
+    redis::pipe()
+        .hset("telescope:subs", sub, "")
+        .hset(format!("telescope:sub:{sub}"), CHAT_ID, 0)
+        .sadd(format!("telescope:chat:{CHAT_ID}"), sub)
+        .exec_async(&mut db).await.unwrap();
+
+    telegram.dispatch(&db, telegram::owner_text(3, &format!("/ban {CHAT_ID}"))).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].params["chat_id"], OWNER_ID);
+    assert_eq!(calls[0].params["text"], "Banned 42 and removed 1 subscriptions");
+    let subs: Vec<String> = db.smembers(format!("telescope:chat:{CHAT_ID}")).await.unwrap();
+    assert!(subs.is_empty());
+    let live_id: Option<String> = db.hget("telescope:subs", sub).await.unwrap();
+    assert_eq!(live_id, None);
+
+    // the messages of a banned chat are ignored
+    telegram.dispatch(&db, telegram::text(4, "/list")).await;
+    assert!(telegram.calls().await.is_empty());
+
+    telegram.dispatch(&db, telegram::owner_text(5, &format!("/unban {CHAT_ID}"))).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls[0].params["text"], "Unbanned 42");
+    telegram.dispatch(&db, telegram::text(6, "/list")).await;
+    assert_eq!(telegram.calls().await.len(), 1);
+    env::remove_var("BOT_OWNER");
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis database at TEST_REDIS_URL"]
+async fn stats_and_broadcast() {
+    let _database = DATABASE.lock().await;
+    let mut db = mock::redis().await;
+    let telegram = FakeTelegram::start().await;
+    env::set_var("BOT_OWNER", OWNER_ID.to_string());
+    let sub = "Bilibili Live:7654321:望远镜";
+    redis::pipe()
+        .hset("telescope:subs", sub, "")
+        .hset(format!("telescope:sub:{sub}"), CHAT_ID, 0)
+        .sadd(format!("telescope:chat:{CHAT_ID}"), sub)
+        .exec_async(&mut db).await.unwrap();
+
+    telegram.dispatch(&db, telegram::owner_text(1, "/stats")).await;
+    let calls = telegram.calls().await;
+    let text = calls[0].params["text"].as_str().unwrap();
+    assert!(text.starts_with("Chats: 1 \\(0 banned\\)\n"), "{text}");
+    assert!(text.contains("\nBilibili Live: 1 subscriptions, 0 live\n"), "{text}");
+
+    telegram.dispatch(&db, telegram::owner_text(2, "/broadcast Maintenance tonight")).await;
+    // the broadcast is sent in the background, possibly before the reply to the owner
+    let mut calls = vec![];
+    for _ in 0..50 {
+        calls.extend(telegram.calls().await);
+        if calls.len() == 3 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let texts: Vec<_> = calls.iter()
+        .map(|call| (call.params["chat_id"].clone(), call.params["text"].clone()))
+        .collect();
+    assert_eq!(texts.len(), 3, "{texts:?}");
+    assert!(texts.contains(&(OWNER_ID.into(), "Broadcasting to 1 chats".into())), "{texts:?}");
+    assert!(texts.contains(&(CHAT_ID.to_string().into(), "Maintenance tonight".into())), "{texts:?}");
+    assert_eq!(texts[2], (OWNER_ID.to_string().into(), "Broadcast sent to 1 chats, 0 failed".into()));
+    env::remove_var("BOT_OWNER");
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis database at TEST_REDIS_URL"]
+async fn access() {
+    let _database = DATABASE.lock().await;
+    let mut db = mock::redis().await;
+    let telegram = FakeTelegram::start().await;
+    env::set_var("BOT_OWNER", OWNER_ID.to_string());
+    env::set_var("INVITE_ONLY", "on");
+    telegram.dispatch(&db, telegram::text(1, "/list")).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls[0].params["text"], "This bot is private, please use the invite link given by its owner\\.");
+
+    telegram.dispatch(&db, telegram::owner_text(2, "/invite")).await;
+    let calls = telegram.calls().await;
+    let text = calls[0].params["text"].as_str().unwrap();
+    let (link, code) = text.rsplit_once("\\=").unwrap();
+    assert_eq!(link, "Invite link, valid for a week: https://t\\.me/telescope\\_bot?start");
+    telegram.dispatch(&db, telegram::text(3, &format!("/start {code}"))).await;
+    let calls = telegram.calls().await;
+    assert_eq!(
+        calls[0].params["text"],
+        "Your invite has been accepted\\. Welcome to the Telescope bot\\. \
+        You can view a list of available commands using the /help command\\."
+    );
+    telegram.dispatch(&db, telegram::text(4, "/list")).await;
+    let calls = telegram.calls().await;
+    assert_eq!(
+        calls[0].params["text"],
+        "You have no subscriptions\\.\nUse the /sub command to add new subscriptions\\."
+    );
+
+    // the user has sent three commands
+    env::set_var("COMMAND_RATE_LIMIT", "3");
+    telegram.dispatch(&db, telegram::text(5, "/list")).await;
+    let calls = telegram.calls().await;
+    assert_eq!(calls[0].params["text"], "You are sending commands too quickly, please wait a minute\\.");
+    telegram.dispatch(&db, telegram::text(6, "/list")).await;
+    assert!(telegram.calls().await.is_empty());
+    // the counter expires at the end of the window
+    let ttl: i64 = db.pttl(format!("telescope:rate_limit:{CHAT_ID}")).await.unwrap();
+    assert!(ttl > 0 && ttl <= 60_000);
+    for key in ["BOT_OWNER", "INVITE_ONLY", "COMMAND_RATE_LIMIT"] {
+        env::remove_var(key);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis database at TEST_REDIS_URL"]
+async fn failover() {
+    let _database = DATABASE.lock().await;
+    let mut db = mock::redis().await;
+    let platform = Platform::TwitterSpace;
+    assert!(lease::acquire(&mut db, &platform, "first", lease::TTL).await.unwrap());
+    assert!(!lease::acquire(&mut db, &platform, "second", lease::TTL).await.unwrap());
+    assert!(lease::acquire(&mut db, &platform, "first", lease::TTL).await.unwrap());
+    // only the holder can give up the lease
+    assert!(!lease::release(&mut db, &platform, "second").await.unwrap());
+    assert!(lease::release(&mut db, &platform, "first").await.unwrap());
+    assert!(lease::acquire(&mut db, &platform, "second", lease::TTL).await.unwrap());
+    assert!(!lease::acquire(&mut db, &platform, "first", lease::TTL).await.unwrap());
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis database at TEST_REDIS_URL"]
+async fn lease_expiry() {
+    let _database = DATABASE.lock().await;
+    let mut db = mock::redis().await;
+    let platform = Platform::BilibiliLive;
+    let ttl = std::time::Duration::from_millis(500);
+    assert!(lease::acquire(&mut db, &platform, "first", ttl).await.unwrap());
+    assert!(!lease::acquire(&mut db, &platform, "second", ttl).await.unwrap());
+    // renewing extends the lease
+    time::sleep(ttl / 2).await;
+    assert!(lease::acquire(&mut db, &platform, "first", ttl).await.unwrap());
+    time::sleep(ttl / 2).await;
+    assert!(!lease::acquire(&mut db, &platform, "second", ttl).await.unwrap());
+
+    // the lease of an instance which stopped renewing it expires, and another instance takes over
+    time::sleep(ttl).await;
+    assert!(lease::acquire(&mut db, &platform, "second", ttl).await.unwrap());
+    assert!(!lease::acquire(&mut db, &platform, "first", ttl).await.unwrap());
+    assert!(!lease::release(&mut db, &platform, "first").await.unwrap());
+    let holder: Option<String> = db.get("telescope:lease:bilibili").await.unwrap();
+    assert_eq!(holder.as_deref(), Some("second"));
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis database at TEST_REDIS_URL"]
+async fn migrate() {
+    let _database = DATABASE.lock().await;
+    let mut db = mock::redis().await;
+    // subscribed by the version 1, which had no prefix
+    let sub = "Bilibili Live:7654321:望远镜";
+    redis::pipe()
+        .hset("subs", sub, "")
+        .hset(sub, CHAT_ID, 0)
+        .sadd(CHAT_ID, sub)
+        .hset(format!("settings:{CHAT_ID}"), "language", "en")
+        .exec_async(&mut db).await.unwrap();
+    // keys of another application sharing the database, which look like keys of the bot
+    let foreign = ["99", "12:34", "live:room", "session:abc", "Twitter Space:1:other"];
+    for key in foreign {
+        let _: () = db.set(key, "").await.unwrap();
+    }
+    assert_eq!(schema::stored_version(&mut db).await.unwrap(), Some(1));
+
+    let migration = schema::migrate(&mut db, true).await.unwrap();
+    assert_eq!(migration.renamed, [
+        (CHAT_ID.to_string(), format!("telescope:chat:{CHAT_ID}")),
+        (sub.to_owned(), format!("telescope:sub:{sub}")),
+        (format!("settings:{CHAT_ID}"), format!("telescope:settings:{CHAT_ID}")),
+        ("subs".to_owned(), "telescope:subs".to_owned())
+    ]);
+    let exists: bool = db.exists("subs").await.unwrap();
+    assert!(exists);
+    assert_eq!(schema::stored_version(&mut db).await.unwrap(), Some(1));
+
+    schema::migrate(&mut db, false).await.unwrap();
+    assert_eq!(schema::stored_version(&mut db).await.unwrap(), Some(schema::VERSION));
+    let mut keys: Vec<String> = db.keys("*").await.unwrap();
+    keys.sort();
+    let mut expected: Vec<String> = foreign.map(str::to_owned).into();
+    expected.extend([
+        format!("telescope:chat:{CHAT_ID}"),
+        "telescope:schema_version".to_owned(),
+        format!("telescope:settings:{CHAT_ID}"),
+        format!("telescope:sub:{sub}"),
+        "telescope:subs".to_owned()
+    ]);
+    expected.sort();
+    assert_eq!(keys, expected);
+    let subs: Vec<String> = db.smembers(format!("telescope:chat:{CHAT_ID}")).await.unwrap();
+    assert_eq!(subs, [sub]);
+    // the migration is only applied once
+    assert!(schema::migrate(&mut db, false).await.unwrap().renamed.is_empty());
+}
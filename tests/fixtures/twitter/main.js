@@ -0,0 +1 @@
+(self.webpackChunk_twitter_responsive_web=self.webpackChunk_twitter_responsive_web||[]).push([["main"],{12345:e=>{e.exports={queryId:"rC8DT6zdR9o2ChNXr2PMKw",operationName:"AudioSpaceById",operationType:"query",metadata:{featureSwitches:["spaces_2022_h2_clipping","spaces_2022_h2_spaces_communities","responsive_web_graphql_exclude_directive_enabled","verified_phone_label_enabled","creator_subscriptions_tweet_preview_api_enabled","responsive_web_graphql_timeline_navigation_enabled","spaces_2025_h1_new_feature"],fieldToggles:[]}}},23456:e=>{e.exports={queryId:"9zwVLJ48lmVUk8u_Gh9DmA",operationName:"ProfileSpotlightsQuery",operationType:"query",metadata:{featureSwitches:[],fieldToggles:[]}}},34567:e=>{e.exports={queryId:"Yka-W8dz7RaEuQNkroPkYw",operationName:"UserByScreenName",operationType:"query",metadata:{featureSwitches:["hidden_profile_subscriptions_enabled","rweb_video_timestamps_enabled"],fieldToggles:["withAuxiliaryUserLabels"]}}},45678:(e,t,n)=>{"use strict";n.d(t,{Z:()=>r});const r=n(12345)}}]);
@@ -0,0 +1,51 @@
+use serde_json::Value;
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock,
+    MockServer,
+    ResponseTemplate
+};
+
+pub const ROOM_ID: &str = "7654321";
+
+/// Fake Bilibili API server, giving the WBI key and the info of a single room
+pub struct FakeBilibili {
+    server: MockServer
+}
+
+/// `getInfoByRoom` response of the room, running if `live`
+pub fn room(live: bool) -> Value {
+    let mut room: Value = serde_json::from_str(include_str!("../fixtures/bilibili/get_info_by_room.json")).unwrap();
+    if !live {
+        room["data"]["room_info"]["live_status"] = 0.into();
+        room["data"]["room_info"]["live_start_time"] = 0.into();
+    }
+    room
+}
+
+impl FakeBilibili {
+    pub async fn start(room: Value) -> Self {
+        let fake = Self { server: MockServer::start().await };
+        fake.set_room(room).await;
+        fake
+    }
+
+    pub fn api_url(&self) -> String {
+        self.server.uri()
+    }
+
+    pub fn nav_url(&self) -> String {
+        format!("{}/x/web-interface/nav", self.server.uri())
+    }
+
+    pub async fn set_room(&self, room: Value) {
+        self.server.reset().await;
+        let nav: Value = serde_json::from_str(include_str!("../fixtures/bilibili/nav.json")).unwrap();
+        Mock::given(method("GET")).and(path("/x/web-interface/nav"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(nav))
+            .mount(&self.server).await;
+        Mock::given(method("GET")).and(path("/xlive/web-room/v1/index/getInfoByRoom")).and(query_param("room_id", ROOM_ID))
+            .respond_with(ResponseTemplate::new(200).set_body_json(room))
+            .mount(&self.server).await;
+    }
+}
@@ -0,0 +1,19 @@
+//! Fake servers of the platforms and of the Telegram Bot API for the end-to-end tests, which run against the Redis
+//! database at `TEST_REDIS_URL`, e.g. `redis://127.0.0.1/15`. The database is flushed by each test, so it is kept
+//! apart from the `REDIS_URL` of the bot, and the tests are ignored unless they are run with `--ignored`.
+
+use std::env;
+
+use redis::aio::MultiplexedConnection;
+
+pub mod bilibili;
+pub mod telegram;
+pub mod twitter;
+
+/// Connection to the empty test database
+pub async fn redis() -> MultiplexedConnection {
+    let url = env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL must be set to a Redis database which can be flushed");
+    let mut db = redis::Client::open(url).unwrap().get_multiplexed_async_connection().await.unwrap();
+    let _: () = redis::cmd("FLUSHDB").query_async(&mut db).await.unwrap();
+    db
+}
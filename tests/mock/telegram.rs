@@ -0,0 +1,200 @@
+use std::{
+    ops::ControlFlow,
+    sync::atomic::{AtomicI32, AtomicUsize, Ordering}
+};
+
+use redis::aio::MultiplexedConnection;
+use serde_json::{json, Map, Value};
+use telescope::{handlers, Bot};
+use teloxide::{
+    prelude::{Requester, RequesterExt},
+    types::{ParseMode, Update}
+};
+use wiremock::{matchers::method, Mock, MockServer, Request, Respond, ResponseTemplate};
+
+pub const CHAT_ID: i64 = 42;
+/// User set as `BOT_OWNER` by the tests of the owner commands
+pub const OWNER_ID: i64 = 7;
+const TOKEN: &str = "123456:TEST";
+
+/// Call of a Bot API method received by the fake server
+#[derive(Debug)]
+pub struct Call {
+    pub method: String,
+    pub params: Value
+}
+
+/// Fake Bot API server, answering every method with a successful result and recording the calls
+pub struct FakeTelegram {
+    server: MockServer,
+    /// Calls already returned by [`FakeTelegram::calls`]
+    seen: AtomicUsize
+}
+
+/// Answers with the bot for `getMe`, a new message for the methods sending one, the edited message for the methods
+/// editing one and `true` for the others
+struct Api {
+    next_message_id: AtomicI32
+}
+
+impl Respond for Api {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let call = Call::from(request);
+        let chat_id = |params: &Value| match &params["chat_id"] {
+            Value::String(id) => id.parse().unwrap(),
+            id => id.as_i64().unwrap()
+        };
+        let result = match call.method.as_str() {
+            "getMe" => json!({
+                "id": 123456,
+                "is_bot": true,
+                "first_name": "Telescope",
+                "username": "telescope_bot",
+                "can_join_groups": true,
+                "can_read_all_group_messages": false,
+                "supports_inline_queries": false,
+                "has_main_web_app": false
+            }),
+            "sendMessage" | "sendPhoto" | "sendDocument" => {
+                message(chat_id(&call.params), self.next_message_id.fetch_add(1, Ordering::SeqCst))
+            }
+            "editMessageText" | "editMessageCaption" => {
+                message(chat_id(&call.params), call.params["message_id"].as_i64().unwrap() as i32)
+            }
+            _ => json!(true)
+        };
+        ResponseTemplate::new(200).set_body_json(json!({"ok": true, "result": result}))
+    }
+}
+
+impl From<&Request> for Call {
+    fn from(request: &Request) -> Self {
+        // teloxide capitalizes the method names, which the Bot API accepts in any case
+        let mut method = request.url.path().rsplit('/').next().unwrap().to_owned();
+        method[..1].make_ascii_lowercase();
+        let content_type = request.headers.get("content-type").and_then(|value| value.to_str().ok()).unwrap_or("");
+        let params = match content_type.split_once("boundary=") {
+            Some((_, boundary)) => multipart(&String::from_utf8_lossy(&request.body), boundary),
+            None => serde_json::from_slice(&request.body).unwrap_or(Value::Null)
+        };
+        Self { method, params }
+    }
+}
+
+/// Fields of a multipart form, files being replaced by their name
+fn multipart(body: &str, boundary: &str) -> Value {
+    let mut fields = Map::new();
+    for part in body.split(&format!("--{boundary}")) {
+        let Some((headers, value)) = part.split_once("\r\n\r\n") else {
+            continue;
+        };
+        let attribute = |name: &str| {
+            let start = headers.find(&format!("{name}=\""))? + name.len() + 2;
+            Some(headers[start..start + headers[start..].find('"')?].to_owned())
+        };
+        let Some(name) = attribute("name") else {
+            continue;
+        };
+        let value = attribute("filename").unwrap_or_else(|| value.trim_end_matches("\r\n").to_owned());
+        // JSON parameters like `reply_parameters` are serialized as strings
+        fields.insert(name, serde_json::from_str(&value).unwrap_or(Value::String(value)));
+    }
+    // files are given as a reference to the part holding them
+    let files: Vec<_> = fields.iter()
+        .filter_map(|(name, value)| Some((name.clone(), value.as_str()?.strip_prefix("attach://")?.to_owned())))
+        .collect();
+    for (name, part) in files {
+        let file = fields.remove(&part).unwrap_or_default();
+        fields.insert(name, file);
+    }
+    Value::Object(fields)
+}
+
+fn message(chat_id: i64, message_id: i32) -> Value {
+    json!({
+        "message_id": message_id,
+        "date": 1735700000,
+        "chat": {"id": chat_id, "type": "private", "first_name": "Tester"},
+        "text": ""
+    })
+}
+
+fn user(id: i64) -> Value {
+    json!({"id": id, "is_bot": false, "first_name": "Tester", "language_code": "en"})
+}
+
+/// Update parsed like the ones received by the bot, teloxide doesn't parse the updates given as a `Value`
+fn update(update: Value) -> Update {
+    serde_json::from_str(&update.to_string()).unwrap()
+}
+
+fn private_message(update_id: i32, user_id: i64, text: &str) -> Update {
+    update(json!({
+        "update_id": update_id,
+        "message": {
+            "message_id": update_id,
+            "date": 1735700000,
+            "chat": {"id": user_id, "type": "private", "first_name": "Tester"},
+            "from": user(user_id),
+            "text": text
+        }
+    }))
+}
+
+/// Private message of the user
+pub fn text(update_id: i32, text: &str) -> Update {
+    private_message(update_id, CHAT_ID, text)
+}
+
+/// Private message of the owner
+pub fn owner_text(update_id: i32, text: &str) -> Update {
+    private_message(update_id, OWNER_ID, text)
+}
+
+/// Press of an inline keyboard button of a message of the bot
+pub fn callback(update_id: i32, message_id: i32, data: &str) -> Update {
+    update(json!({
+        "update_id": update_id,
+        "callback_query": {
+            "id": update_id.to_string(),
+            "from": user(CHAT_ID),
+            "chat_instance": "1",
+            "data": data,
+            "message": {
+                "message_id": message_id,
+                "date": 1735700000,
+                "chat": {"id": CHAT_ID, "type": "private", "first_name": "Tester"},
+                "text": ""
+            }
+        }
+    }))
+}
+
+impl FakeTelegram {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(Api { next_message_id: AtomicI32::new(100) })
+            .mount(&server).await;
+        Self { server, seen: AtomicUsize::new(0) }
+    }
+
+    pub fn bot(&self) -> Bot {
+        teloxide::Bot::new(TOKEN).set_api_url(self.server.uri().parse().unwrap()).parse_mode(ParseMode::MarkdownV2)
+    }
+
+    /// Handle the update like the dispatcher of the bot
+    pub async fn dispatch(&self, db: &MultiplexedConnection, update: Update) {
+        let me = self.bot().get_me().await.unwrap();
+        let deps = dptree::deps![self.bot(), db.clone(), me, update];
+        assert!(matches!(handlers::schema().dispatch(deps).await, ControlFlow::Break(Ok(()))));
+    }
+
+    /// Calls received since the last time, except `getMe` which is made by the harness
+    pub async fn calls(&self) -> Vec<Call> {
+        let requests = self.server.received_requests().await.unwrap();
+        let calls: Vec<_> = requests.iter().map(Call::from).collect();
+        let seen = self.seen.swap(calls.len(), Ordering::SeqCst);
+        calls.into_iter().skip(seen).filter(|call| call.method != "getMe").collect()
+    }
+}
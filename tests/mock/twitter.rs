@@ -0,0 +1,86 @@
+use serde_json::Value;
+use wiremock::{
+    matchers::{method, path, path_regex},
+    Mock,
+    MockServer,
+    ResponseTemplate
+};
+
+pub const SCREEN_NAME: &str = "telescope_dev";
+pub const SPACE_ID: &str = "1YqKDqDXAbwKV";
+/// User speaking in the Space of another user
+pub const GUEST_ID: &str = "555666777";
+
+/// Fake Twitter API server, with a single user hosting a single Space. The web client isn't served, so the built-in
+/// GraphQL operations are used.
+pub struct FakeTwitter {
+    server: MockServer
+}
+
+fn fixture(json: &str) -> Value {
+    serde_json::from_str(json).unwrap()
+}
+
+/// `AudioSpaceById` response of the Space, running if `live`
+pub fn space(live: bool) -> Value {
+    let mut space = fixture(include_str!("../fixtures/twitter/audio_space_by_id.json"));
+    if !live {
+        let metadata = &mut space["data"]["audioSpace"]["metadata"];
+        metadata["state"] = "Ended".into();
+        metadata["ended_at"] = "1735704000000".into();
+    }
+    space
+}
+
+impl FakeTwitter {
+    pub async fn start(space: Value) -> Self {
+        let fake = Self { server: MockServer::start().await };
+        fake.set_space(space).await;
+        fake
+    }
+
+    pub fn api_url(&self) -> String {
+        format!("{}/i/api/", self.server.uri())
+    }
+
+    pub fn web_url(&self) -> String {
+        format!("{}/", self.server.uri())
+    }
+
+    pub async fn set_space(&self, space: Value) {
+        let live = space["data"]["audioSpace"]["metadata"]["state"] == "Running";
+        let mut avatar_content = fixture(include_str!("../fixtures/twitter/avatar_content.json"));
+        if !live {
+            avatar_content["users"]["123456789"]["spaces"] = serde_json::json!({});
+        }
+        self.mount(space, avatar_content).await;
+    }
+
+    /// Serve the running Space as one the user [`GUEST_ID`] speaks in, its creator not being polled
+    pub async fn set_appearance(&self) {
+        let mut avatar_content = fixture(include_str!("../fixtures/twitter/avatar_content.json"));
+        let users = avatar_content["users"].as_object_mut().unwrap();
+        let host = users.remove("123456789").unwrap();
+        users.insert(GUEST_ID.to_owned(), host);
+        self.mount(space(true), avatar_content).await;
+    }
+
+    async fn mount(&self, space: Value, avatar_content: Value) {
+        self.server.reset().await;
+        let responses = [
+            (path_regex(r"^/i/api/graphql/[\w-]+/ProfileSpotlightsQuery$"),
+                fixture(include_str!("../fixtures/twitter/profile_spotlights_query.json"))),
+            (path_regex(r"^/i/api/graphql/[\w-]+/AudioSpaceById$"), space),
+            (path_regex(r"^/i/api/fleets/v1/avatar_content$"), avatar_content),
+            (path_regex(r"^/i/api/1\.1/live_video_stream/status/[\w-]+$"),
+                fixture(include_str!("../fixtures/twitter/live_video_stream_status.json")))
+        ];
+        for (matcher, response) in responses {
+            Mock::given(method("GET")).and(matcher)
+                .respond_with(ResponseTemplate::new(200).set_body_json(response))
+                .mount(&self.server).await;
+        }
+        // the web client is missing
+        Mock::given(method("GET")).and(path("/")).respond_with(ResponseTemplate::new(404)).mount(&self.server).await;
+    }
+}